@@ -3,12 +3,18 @@ use std::num::TryFromIntError;
 use ansi_term::Color::Fixed;
 
 use parser::ast::{Expression, Type};
+use parser::KEYWORDS;
 use utilities::cursor::Cursor;
 use utilities::message::MessageType::*;
 use utilities::message::{details, Message};
-use utilities::Cyan;
+use utilities::suggest::{did_you_mean, similar};
 
+use crate::datapack::{path_segment, CompiledUnit, Function, ResourceLocation};
 use crate::environment::{Context, McFunction, Scope, Value};
+use crate::lint::{self, Lint, Lints};
+use crate::liveness;
+use crate::module::Imports;
+use crate::target::{Feature, Target};
 
 /*fn ast_type_to_type(ast_type: &ast::Type) -> Result<Type, Message> {
     match ast_type.name.as_str() {
@@ -37,22 +43,79 @@ impl MathOp {
         }
     }
 
-    fn execute(&self, left: i32, right: i32) -> i32 {
+    /// The result of the operation, unless it overflows or divides by zero.
+    fn execute(&self, left: i32, right: i32) -> Option<i32> {
         match self {
-            | MathOp::Addition => left + right,
-            | MathOp::Subtraction => left - right,
-            | MathOp::Multiplication => left * right,
-            | MathOp::Division => left / right,
+            | MathOp::Addition => left.checked_add(right),
+            | MathOp::Subtraction => left.checked_sub(right),
+            | MathOp::Multiplication => left.checked_mul(right),
+            | MathOp::Division => left.checked_div(right),
         }
     }
 }
 
+/// An error for a name that isn't declared, suggesting similar names that are,
+/// or keywords.
+fn unknown_member(scope: &Scope, kind: &str, name: &str, cursor: &Cursor) -> Message {
+    let error = Message::error(
+        UnknownMember,
+        details::UnknownMember!(kind, name),
+        cursor.clone(),
+    );
+    let names = scope.visible_names();
+    let candidates = names.iter().copied().chain(KEYWORDS);
+    match did_you_mean(&similar(name, candidates)) {
+        | Some(help) => error.with_help(&help),
+        | None => error,
+    }
+}
+
+#[derive(Clone)]
+pub struct Options {
+    /// Loops with a constant trip count up to this limit are unrolled
+    pub unroll_limit: usize,
+    pub target: Target,
+    pub lints: Lints,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            unroll_limit: 16,
+            target: Target::default(),
+            lints: Lints::default(),
+        }
+    }
+}
+
+/// The parts of a `for` loop being compiled.
+struct ForLoop {
+    variable: String,
+    start: Expression,
+    end: Expression,
+    body: Vec<Expression>,
+    cursor: Cursor,
+}
+
 struct Compiler {
     functions: Vec<McFunction>,
+    warnings: Vec<Message>,
+    /// Errors of statements that failed, compilation carrying on after them
+    errors: Vec<Message>,
+    options: Options,
+    /// Functions of other modules the program imports
+    imports: Imports,
+    loop_count: usize,
 }
 impl Compiler {
-    fn new() -> Self {
-        Self { functions: vec![] }
+    fn new(options: Options, imports: Imports) -> Self {
+        Self {
+            functions: vec![],
+            warnings: vec![],
+            errors: vec![],
+            options,
+            imports,
+            loop_count: 0,
+        }
     }
 
     fn compile_variable_declaration(
@@ -64,18 +127,20 @@ impl Compiler {
         variable_type: Option<Type>,
         value: Option<Box<Expression>>,
     ) -> Result<Value, Message> {
-        let player = format!(
-            "${}",
-            name
-        );
-        let scoreboard = scope.function.name.clone();
-        if scope.symbol_table.contains(&name) {
+        let player = scope.local(&name);
+        let scoreboard = scope.function.objective.clone();
+        if let Some(declaration) = scope.symbol_table.get(&name) {
             return Err(
                 Message::error(
                     MemberRedeclaration,
                     details::MemberRedeclaration!("Variable", name),
                     cursor.clone(),
-                ),
+                )
+                .with_label(declaration.clone(), "first declared here")
+                .with_help(&format!(
+                    "assign to it with '{} = ...' instead",
+                    name
+                )),
             );
         }
         if let Some(value) = value {
@@ -92,41 +157,27 @@ impl Compiler {
                 value.clone(),
             )?;
             if expr == reference {
+                scope.symbol_table.insert(name, cursor);
                 return Ok(reference);
             }
-            scope.function.push_cmd(
-                match expr {
-                    | Value::Int(int) => {
-                        format!(
-                            "scoreboard players set {} {} {}",
-                            &player, &scoreboard, int
-                        )
-                    }
-                    | Value::IntReference {
-                        scoreboard: other_scoreboard,
-                        player: other_player,
-                    } => format!(
-                        "scoreboard players operation {} {} = {} {}",
-                        &player, &scoreboard, other_player, other_scoreboard
-                    ),
-                    | val => {
-                        return Err(
-                            Message::error(
-                                TypeMismatch,
-                                details::TypeMismatch!(
-                                    "int",
-                                    val.name()
-                                ),
-                                value.get_cursor().clone(),
-                            ),
-                        )
-                    }
-                },
-            );
-            scope.symbol_table.insert(name);
+            scope.write(&player);
+            if let Err(val) = self.store_value(scope, expr, &player, &scoreboard) {
+                return Err(
+                    Message::error(
+                        TypeMismatch,
+                        details::TypeMismatch!(
+                            "int",
+                            val.name()
+                        ),
+                        value.get_cursor().clone(),
+                    )
+                    .with_note("variables can only hold ints"),
+                );
+            }
+            scope.symbol_table.insert(name, cursor);
             Ok(reference)
         } else {
-            scope.symbol_table.insert(name);
+            scope.symbol_table.insert(name, cursor);
             Ok(Value::UndefinedReference { scoreboard, player })
         }
     }
@@ -139,20 +190,12 @@ impl Compiler {
         name: String,
         value: Box<Expression>
     ) -> Result<Value, Message> {
-        let player = format!(
-            "${}",
-            name
-        );
-        let scoreboard = scope.function.name.clone();
-        if !scope.symbol_table.contains(&name) {
-            return Err(
-                Message::error(
-                    UnknownMember,
-                    details::UnknownMember!("Variable", name),
-                    cursor.clone(),
-                ),
-            );
-        }
+        let player = scope.local(&name);
+        let scoreboard = scope.function.objective.clone();
+        let declaration = match scope.symbol_table.get(&name) {
+            | Some(declaration) => declaration.clone(),
+            | None => return Err(unknown_member(scope, "Variable", &name, &cursor)),
+        };
         let value = *value;
         let reference = Value::IntReference {
             scoreboard: scoreboard.clone(),
@@ -168,35 +211,26 @@ impl Compiler {
         if expr == reference {
             return Ok(reference);
         }
-        scope.function.push_cmd(
-            match expr {
-                | Value::Int(int) => {
-                    format!(
-                        "scoreboard players set {} {} {}",
-                        &player, &scoreboard, int
-                    )
-                }
-                | Value::IntReference {
-                    scoreboard: other_scoreboard,
-                    player: other_player,
-                } => format!(
-                    "scoreboard players operation {} {} = {} {}",
-                    &player, &scoreboard, other_player, other_scoreboard
+        scope.write(&player);
+        if let Err(val) = self.store_value(scope, expr, &player, &scoreboard) {
+            return Err(
+                Message::error(
+                    TypeMismatch,
+                    details::TypeMismatch!(
+                        "int",
+                        val.name()
+                    ),
+                    value.get_cursor().clone(),
+                )
+                .with_label(
+                    declaration,
+                    &format!(
+                        "'{}' is declared here, as an int",
+                        name
+                    ),
                 ),
-                | val => {
-                    return Err(
-                        Message::error(
-                            TypeMismatch,
-                            details::TypeMismatch!(
-                                "int",
-                                val.name()
-                            ),
-                            value.get_cursor().clone(),
-                        ),
-                    )
-                }
-            },
-        );
+            );
+        }
         Ok(reference)
     }
 
@@ -220,24 +254,17 @@ impl Compiler {
         scope: &Scope,
         identifier: String,
     ) -> Result<Value, Message> {
-        if scope.symbol_table.contains(&identifier) {
+        if let Some(int) = scope.constants.get(&identifier) {
+            Ok(Value::Int(*int))
+        } else if scope.symbol_table.contains_key(&identifier) {
             Ok(
                 Value::IntReference {
-                    scoreboard: scope.function.name.clone(),
-                    player: format!(
-                        "${}",
-                        identifier
-                    ),
+                    scoreboard: scope.function.objective.clone(),
+                    player: scope.local(&identifier),
                 },
             )
         } else {
-            Err(
-                Message::error(
-                    UnknownMember,
-                    details::UnknownMember!("Variable", identifier),
-                    cursor.clone(),
-                ),
-            )
+            Err(unknown_member(scope, "Variable", &identifier, &cursor))
         }
     }
 
@@ -265,26 +292,159 @@ impl Compiler {
             | MathOp::Multiplication => {
                 scope.function.push_cmd(format!(
                     "scoreboard players set %{} {} {}",
-                    value, scope.function.name, value
+                    value, scope.function.objective, value
                 ));
                 scope.function.push_cmd(format!(
                     "scoreboard players operation {} {} *= %{} {}",
-                    player, scoreboard, value, scope.function.name
+                    player, scoreboard, value, scope.function.objective
                 ));
             }
             | MathOp::Division => {
                 scope.function.push_cmd(format!(
                     "scoreboard players set %{} {} {}",
-                    value, scope.function.name, value
+                    value, scope.function.objective, value
                 ));
                 scope.function.push_cmd(format!(
                     "scoreboard players operation {} {} /= %{} {}",
-                    player, scoreboard, value, scope.function.name
+                    player, scoreboard, value, scope.function.objective
                 ));
             }
         }
     }
 
+    fn store_result(
+        &self,
+        scope: &mut Scope,
+        command: &str,
+        player: &str,
+        scoreboard: &str,
+    ) {
+        scope.function.push_cmd(format!(
+            "execute store result score {} {} run {}",
+            player, scoreboard, command
+        ));
+    }
+
+    /// Writes an int value to a score, storing the result of a command straight
+    /// into it rather than through a temporary. Values that aren't ints are
+    /// given back.
+    fn store_value(
+        &self,
+        scope: &mut Scope,
+        value: Value,
+        player: &str,
+        scoreboard: &str,
+    ) -> Result<(), Value> {
+        let command = match value {
+            | Value::Int(int) => format!(
+                "scoreboard players set {} {} {}",
+                player, scoreboard, int
+            ),
+            | Value::IntReference {
+                scoreboard: other_scoreboard,
+                player: other_player,
+            } => format!(
+                "scoreboard players operation {} {} = {} {}",
+                player, scoreboard, other_player, other_scoreboard
+            ),
+            | Value::Command(command) => {
+                self.store_result(
+                    scope,
+                    &command,
+                    player,
+                    scoreboard,
+                );
+                return Ok(());
+            }
+            | value => return Err(value),
+        };
+        scope.function.push_cmd(command);
+        Ok(())
+    }
+
+    /// Runs a pending command and stores its result, into the macro target
+    /// when there is one. Other values are returned unchanged.
+    fn materialize(&self, scope: &mut Scope, context: &Context, value: Value) -> Value {
+        match value {
+            | Value::Command(command) => {
+                let (player, scoreboard) = self.output_slot(
+                    scope, context, None, None,
+                );
+                self.store_result(
+                    scope,
+                    &command,
+                    &player,
+                    &scoreboard,
+                );
+                Value::IntReference { scoreboard, player }
+            }
+            | value => value,
+        }
+    }
+
+    /// Picks where the result of a math operation is written: the macro target
+    /// unless it would be overwritten before the right operand is read, otherwise the left operand's
+    /// temporary or a fresh one.
+    fn output_slot(
+        &self,
+        scope: &mut Scope,
+        context: &Context,
+        left_player: Option<&String>,
+        right_player: Option<&String>,
+    ) -> (String, String) {
+        let (player, scoreboard) = match &context.macro_target {
+            | Some(Value::IntReference { player, scoreboard })
+                if Some(player) != right_player || Some(player) == left_player =>
+            {
+                (
+                    player.to_string(),
+                    scoreboard.to_string(),
+                )
+            }
+            | Some(Value::IntReference { .. }) | None => match left_player {
+                | Some(player) if scope.is_temp(player) && !scope.is_cached(player) => (
+                    player.to_string(),
+                    scope.function.objective.clone(),
+                ),
+                | _ => (
+                    scope.acquire_temp(),
+                    scope.function.objective.clone(),
+                ),
+            },
+            | _ => unreachable!(),
+        };
+        scope.write(&player);
+        (player, scoreboard)
+    }
+
+    /// A textual key identifying a pure math expression, collecting the players
+    /// it reads. Anything else can't be reused.
+    fn subexpression_key(scope: &Scope, expression: &Expression, inputs: &mut Vec<String>) -> Option<String> {
+        let (math_op, left, right) = match expression {
+            | Expression::Integer(int, _) => return Some(int.to_string()),
+            | Expression::VariableAccess(name, _) => {
+                if let Some(int) = scope.constants.get(name) {
+                    return Some(int.to_string());
+                }
+                inputs.push(scope.local(name));
+                return Some(name.clone());
+            }
+            | Expression::Addition { left, right, .. } => (MathOp::Addition, left, right),
+            | Expression::Subtraction { left, right, .. } => (MathOp::Subtraction, left, right),
+            | Expression::Multiplication { left, right, .. } => (MathOp::Multiplication, left, right),
+            | Expression::Division { left, right, .. } => (MathOp::Division, left, right),
+            | _ => return None,
+        };
+        Some(format!(
+            "({}{}{})",
+            Self::subexpression_key(scope, left, inputs)?,
+            math_op.symbol(),
+            Self::subexpression_key(scope, right, inputs)?
+        ))
+    }
+
+    /// Reuses the result of an identical math expression computed earlier in
+    /// the function if none of its inputs changed since.
     fn compile_math_operation(
         &mut self,
         cursor: Cursor,
@@ -294,9 +454,57 @@ impl Compiler {
         left_expr: Expression,
         right_expr: Expression,
     ) -> Result<Value, Message> {
+        let mut inputs: Vec<String> = Vec::new();
+        let key = match (
+            Self::subexpression_key(scope, &left_expr, &mut inputs),
+            Self::subexpression_key(scope, &right_expr, &mut inputs),
+        ) {
+            | (Some(left), Some(right)) => Some(format!(
+                "({}{}{})",
+                left,
+                math_op.symbol(),
+                right
+            )),
+            | _ => None,
+        };
+
+        if let Some(player) = key.as_ref().and_then(|key| scope.cached(key)) {
+            return Ok(
+                Value::IntReference {
+                    scoreboard: scope.function.objective.clone(),
+                    player,
+                },
+            );
+        }
+
+        let value = self.emit_math_operation(
+            cursor, scope, context, math_op, left_expr, right_expr,
+        )?;
+
+        if let (Some(key), Value::IntReference { player, .. }) = (key, &value) {
+            scope.cache(key, player, inputs);
+        }
+
+        Ok(value)
+    }
+
+    fn emit_math_operation(
+        &mut self,
+        cursor: Cursor,
+        scope: &mut Scope,
+        context: &Context,
+        math_op: MathOp,
+        left_expr: Expression,
+        right_expr: Expression,
+    ) -> Result<Value, Message> {
+        let left_cursor = left_expr.get_cursor().clone();
+        let right_cursor = right_expr.get_cursor().clone();
         let left = self.compile_expression(
             scope, context, left_expr,
         )?;
+        let left = self.materialize(
+            scope, context, left,
+        );
         let mut new_context = context.clone();
         new_context.macro_target = None;
         let right = self.compile_expression(
@@ -304,30 +512,49 @@ impl Compiler {
             &new_context,
             right_expr,
         )?;
+        let right = self.materialize(
+            scope,
+            &new_context,
+            right,
+        );
 
         match (
             left, right,
         ) {
-            | (Value::Int(left_val), Value::Int(right_val)) => Ok(
-                Value::Int(
-                    math_op.execute(
-                        left_val, right_val,
-                    ),
-                ),
-            ),
+            | (Value::Int(left_val), Value::Int(right_val)) => match math_op.execute(left_val, right_val) {
+                | Some(int) => Ok(Value::Int(int)),
+                // left for the game to compute, as it would be without folding
+                | None => {
+                    let (temp, temp_scoreboard) = self.output_slot(
+                        scope, context, None, None,
+                    );
+                    scope.function.push_cmd(format!(
+                        "scoreboard players set {} {} {}",
+                        temp, temp_scoreboard, left_val
+                    ));
+                    self.execute_semi_static_math_operation(
+                        scope,
+                        math_op,
+                        &temp,
+                        &temp_scoreboard,
+                        right_val,
+                    );
+                    Ok(
+                        Value::IntReference {
+                            player: temp,
+                            scoreboard: scope.function.objective.to_string(),
+                        },
+                    )
+                }
+            },
 
             | (Value::IntReference { player, scoreboard }, Value::Int(right_val)) => {
-                let (temp, temp_scoreboard) = match &context.macro_target {
-                    | Some(_out @ Value::IntReference { player, scoreboard }) => (
-                        player.to_string(),
-                        scoreboard.to_string(),
-                    ),
-                    | None => (
-                        "$$temp".to_string(),
-                        scope.function.name.clone(),
-                    ),
-                    | _ => unreachable!(),
-                };
+                let (temp, temp_scoreboard) = self.output_slot(
+                    scope,
+                    context,
+                    Some(&player),
+                    None,
+                );
 
                 if (
                     &player,
@@ -350,25 +577,24 @@ impl Compiler {
                     right_val,
                 );
 
+                if player != temp {
+                    scope.release_temp(&player);
+                }
+
                 Ok(
                     Value::IntReference {
                         player: temp.to_string(),
-                        scoreboard: scope.function.name.to_string(),
+                        scoreboard: scope.function.objective.to_string(),
                     },
                 )
             }
             | (Value::Int(left_val), Value::IntReference { player, scoreboard }) => {
-                let (temp, temp_scoreboard) = match &context.macro_target {
-                    | Some(_out @ Value::IntReference { player, scoreboard }) => (
-                        player.to_string(),
-                        scoreboard.to_string(),
-                    ),
-                    | None => (
-                        "$$temp".to_string(),
-                        scope.function.name.clone(),
-                    ),
-                    | _ => unreachable!(),
-                };
+                let (temp, temp_scoreboard) = self.output_slot(
+                    scope,
+                    context,
+                    None,
+                    Some(&player),
+                );
 
                 scope.function.push_cmd(format!(
                     "scoreboard players set {} {} {}",
@@ -384,10 +610,12 @@ impl Compiler {
                     &scoreboard
                 ));
 
+                scope.release_temp(&player);
+
                 Ok(
                     Value::IntReference {
                         player: temp.to_string(),
-                        scoreboard: scope.function.name.to_string(),
+                        scoreboard: scope.function.objective.to_string(),
                     },
                 )
             }
@@ -401,17 +629,12 @@ impl Compiler {
                     scoreboard: right_scoreboard,
                 },
             ) => {
-                let (temp, temp_scoreboard) = match &context.macro_target {
-                    | Some(_out @ Value::IntReference { player, scoreboard }) => (
-                        player.to_string(),
-                        scoreboard.to_string(),
-                    ),
-                    | None => (
-                        "$$temp".to_string(),
-                        scope.function.name.clone(),
-                    ),
-                    | _ => unreachable!(),
-                };
+                let (temp, temp_scoreboard) = self.output_slot(
+                    scope,
+                    context,
+                    Some(&left_player),
+                    Some(&right_player),
+                );
 
                 if (
                     &left_player,
@@ -434,14 +657,36 @@ impl Compiler {
                     &right_player,
                     &right_scoreboard
                 ));
+
+                scope.release_temp(&right_player);
+                if left_player != temp {
+                    scope.release_temp(&left_player);
+                }
+
                 Ok(
                     Value::IntReference {
                         player: temp.to_string(),
-                        scoreboard: scope.function.name.to_string(),
+                        scoreboard: scope.function.objective.to_string(),
                     },
                 )
             }
-            | _ => todo!(),
+            | (left, right) => {
+                let (value, cursor) = match left {
+                    | Value::Int(_) | Value::IntReference { .. } => (right, right_cursor),
+                    | _ => (left, left_cursor),
+                };
+                Err(
+                    Message::error(
+                        TypeMismatch,
+                        details::TypeMismatch!(
+                            "int",
+                            value.name()
+                        ),
+                        cursor,
+                    )
+                    .with_note("math can only be done on ints"),
+                )
+            }
         }
     }
 
@@ -523,55 +768,468 @@ impl Compiler {
         parent: Option<&mut Scope>,
         context: &Context,
         name: String,
+        location: ResourceLocation,
         body: Vec<Expression>,
     ) -> Result<Value, Message> {
-        let mut function = McFunction::new(name.clone());
+        let liveness = liveness::analyse(&body);
+        let mut function = McFunction::new(location.clone(), &self.options.target);
         function.push_cmd(format!(
             "scoreboard objectives add {} dummy",
-            name
+            function.objective
         ));
-        let mut scope: Scope;
 
-        if let Some(parent) = parent {
-            parent.symbol_table.insert(name.clone());
-            scope = Scope::new(
-                &mut function,
-                Some(parent),
-            );
-        }
-        else {
-            scope = Scope::new(
-                &mut function,
-                None,
-            );
+        // the body goes to `tail-call` when it ends with a call to the function
+        // itself, which then runs it again skipping the objective setup. Each
+        // call is still one function deeper, as mcfunctions have no loops, so
+        // this only saves the setup and doesn't lift maxCommandChainLength
+        let mut body_function = McFunction::with_objective(
+            location.child("tail-call"),
+            &function.objective,
+        );
+        let mut tail_call = false;
+
+        {
+            let mut scope: Scope;
+
+            if let Some(parent) = parent {
+                parent.symbol_table.insert(name.clone(), cursor.clone());
+                parent.functions.insert(name.clone(), location.clone());
+                scope = Scope::new(
+                    &mut body_function,
+                    liveness,
+                    Some(parent),
+                );
+            }
+            else {
+                scope = Scope::new(
+                    &mut body_function,
+                    liveness,
+                    None,
+                );
+            }
+            scope.location = location.clone();
+
+            let last = body.len().saturating_sub(1);
+            let mut guarded = false;
+            for (index, expression) in body.into_iter().enumerate() {
+                // a nested function of the same name hides this one
+                let calls_itself = scope.function_location(&name) == Some(&location);
+                if calls_itself && !guarded {
+                    if lint::guards(&expression) {
+                        guarded = true;
+                    }
+                    else if let Some(call_cursor) = Self::self_call(&name, &expression) {
+                        guarded = true;
+                        self.lint(
+                            Lint::UnboundedRecursion,
+                            Message::warning(
+                                UnboundedRecursion,
+                                details::UnboundedRecursion!(name),
+                                call_cursor,
+                            ),
+                        );
+                    }
+                }
+                if calls_itself && index == last {
+                    if let Expression::FunctionCall { name: callee, .. } = &expression {
+                        if callee == &name {
+                            tail_call = true;
+                            continue;
+                        }
+                    }
+                }
+
+                scope.begin_statement(index);
+                self.compile_statement(
+                    &mut scope, context, expression,
+                );
+            }
         }
 
-        for expression in body {
-            self.compile_expression(
-                &mut scope, context, expression,
-            )?;
+        if tail_call {
+            body_function.push_cmd(format!(
+                "function {}",
+                body_function.location
+            ));
+            function.push_cmd(format!(
+                "function {}",
+                body_function.location
+            ));
+            self.functions.push(body_function);
+        }
+        else {
+            function.append(&body_function);
         }
 
         function.push_cmd(format!(
             "scoreboard objectives remove {}",
-            name
+            function.objective
         ));
 
         self.functions.push(function);
         Ok(Value::FunctionReference(name))
     }
 
+    /// Finds a call to the function of the given name in a statement. Calls in
+    /// nested functions are not followed, nor calls after a nested function
+    /// of the same name hides it.
+    fn self_call(name: &str, expression: &Expression) -> Option<Cursor> {
+        match expression {
+            | Expression::FunctionCall {
+                name: callee,
+                arguments,
+                cursor,
+            } => match callee == name {
+                | true => Some(cursor.clone()),
+                | false => arguments
+                    .iter()
+                    .find_map(|argument| Self::self_call(name, &argument.expression)),
+            },
+            | Expression::VariableDeclaration { value, .. } => {
+                value.as_ref().and_then(|value| Self::self_call(name, value))
+            }
+            | Expression::VariableAssign { value, .. } => Self::self_call(name, value),
+            | Expression::Addition { left, right, .. }
+            | Expression::Subtraction { left, right, .. }
+            | Expression::Multiplication { left, right, .. }
+            | Expression::Division { left, right, .. } => {
+                Self::self_call(name, left).or_else(|| Self::self_call(name, right))
+            }
+            | Expression::ForLoop {
+                start, end, body, ..
+            } => Self::self_call(name, start)
+                .or_else(|| Self::self_call(name, end))
+                .or_else(|| {
+                    body.iter()
+                        .take_while(|expression| !matches!(
+                            expression,
+                            Expression::FunctionDeclaration { name: declared, .. } if declared == name
+                        ))
+                        .find_map(|expression| Self::self_call(name, expression))
+                }),
+            | _ => None,
+        }
+    }
+
+    /// Whether a loop body can be expanded once per iteration: it must leave
+    /// the loop variable alone and not declare functions.
+    fn is_unrollable(variable: &str, body: &[Expression]) -> bool {
+        fn visit(variable: &str, expression: &Expression) -> bool {
+            match expression {
+                | Expression::FunctionDeclaration { .. } => false,
+                | Expression::VariableAssign { name, value, .. } => {
+                    name != variable && visit(variable, value)
+                }
+                | Expression::VariableDeclaration { value, .. } => match value {
+                    | Some(value) => visit(variable, value),
+                    | None => true,
+                },
+                | Expression::Addition { left, right, .. }
+                | Expression::Subtraction { left, right, .. }
+                | Expression::Multiplication { left, right, .. }
+                | Expression::Division { left, right, .. } => {
+                    visit(variable, left) && visit(variable, right)
+                }
+                | Expression::ForLoop {
+                    start, end, body, ..
+                } => {
+                    visit(variable, start)
+                        && visit(variable, end)
+                        && body.iter().all(|expression| visit(variable, expression))
+                }
+                | _ => true,
+            }
+        }
+
+        body.iter().all(|expression| visit(variable, expression))
+    }
+
+    fn compile_loop_body(
+        &mut self,
+        scope: &mut Scope,
+        context: &Context,
+        body: Vec<Expression>,
+    ) -> Result<(), Message> {
+        // locals declared in the body don't outlive a single iteration
+        let symbols = scope.symbol_table.clone();
+        for expression in body {
+            self.compile_statement(
+                scope, context, expression,
+            );
+        }
+        scope.symbol_table = symbols;
+        Ok(())
+    }
+
+    fn compile_for_loop(
+        &mut self,
+        scope: &mut Scope,
+        context: &Context,
+        for_loop: ForLoop,
+    ) -> Result<Value, Message> {
+        let ForLoop {
+            variable,
+            start,
+            end,
+            body,
+            cursor,
+        } = for_loop;
+        let mut new_context = context.clone();
+        new_context.macro_target = None;
+
+        let start_cursor = start.get_cursor().clone();
+        let start = self.compile_expression(
+            scope,
+            &new_context,
+            start,
+        )?;
+        let end_cursor = end.get_cursor().clone();
+        let end = self.compile_expression(
+            scope,
+            &new_context,
+            end,
+        )?;
+        let end = self.materialize(
+            scope,
+            &new_context,
+            end,
+        );
+
+        if let Some(declaration) = scope.symbol_table.get(&variable) {
+            return Err(
+                Message::error(
+                    MemberRedeclaration,
+                    details::MemberRedeclaration!("Variable", variable),
+                    cursor,
+                )
+                .with_label(declaration.clone(), "first declared here"),
+            );
+        }
+
+        // unrolling
+
+        if let (Value::Int(first), Value::Int(last)) = (&start, &end) {
+            let trip_count = (*last as i64 - *first as i64).max(0) as usize;
+            if trip_count <= self.options.unroll_limit && Self::is_unrollable(&variable, &body) {
+                scope.symbol_table.insert(variable.clone(), cursor.clone());
+                for value in *first..*last {
+                    scope.constants.insert(variable.clone(), value);
+                    self.compile_loop_body(
+                        scope,
+                        context,
+                        body.clone(),
+                    )?;
+                }
+                scope.constants.remove(&variable);
+                scope.symbol_table.remove(&variable);
+                return Ok(Value::Undefined);
+            }
+        }
+
+        // recursive lowering
+
+        let player = scope.local(&variable);
+        let objective = scope.function.objective.clone();
+
+        scope.write(&player);
+        if let Err(val) = self.store_value(scope, start, &player, &objective) {
+            return Err(
+                Message::error(
+                    TypeMismatch,
+                    details::TypeMismatch!(
+                        "int",
+                        val.name()
+                    ),
+                    start_cursor,
+                )
+                .with_note("loop bounds must be ints"),
+            );
+        }
+
+        let condition = match end {
+            | Value::Int(int) => format!(
+                "matches ..{}",
+                int.saturating_sub(1)
+            ),
+            | Value::IntReference {
+                scoreboard: end_scoreboard,
+                player: end_player,
+            } => {
+                // the bound is evaluated once, so copy it somewhere the body can't reach
+                let bound = if scope.is_temp(&end_player) {
+                    end_player
+                } else {
+                    let temp = scope.acquire_temp();
+                    scope.function.push_cmd(format!(
+                        "scoreboard players operation {} {} = {} {}",
+                        temp, objective, end_player, end_scoreboard
+                    ));
+                    temp
+                };
+                format!(
+                    "< {} {}",
+                    bound, objective
+                )
+            }
+            | val => {
+                return Err(
+                    Message::error(
+                        TypeMismatch,
+                        details::TypeMismatch!(
+                            "int",
+                            val.name()
+                        ),
+                        end_cursor,
+                    )
+                    .with_note("loop bounds must be ints"),
+                )
+            }
+        };
+
+        // `-` can't be part of an identifier, so no declared function is named alike
+        let loop_location = scope.location.child(&format!(
+            "loop-{}",
+            self.loop_count
+        ));
+        self.loop_count += 1;
+
+        let guard = format!(
+            "execute if score {} {} {} run function {}",
+            player, objective, condition, loop_location
+        );
+        scope.function.push_cmd(&guard);
+
+        let mut loop_function = McFunction::with_objective(
+            loop_location,
+            objective.clone(),
+        );
+        std::mem::swap(
+            scope.function,
+            &mut loop_function,
+        );
+
+        // every iteration but the first enters the body with different scores
+        scope.forget();
+        scope.symbol_table.insert(variable.clone(), cursor.clone());
+        let result = self.compile_loop_body(
+            scope, context, body,
+        );
+        scope.symbol_table.remove(&variable);
+        scope.forget();
+
+        scope.function.push_cmd(format!(
+            "scoreboard players add {} {} 1",
+            player, objective
+        ));
+        scope.function.push_cmd(&guard);
+
+        std::mem::swap(
+            scope.function,
+            &mut loop_function,
+        );
+        result?;
+
+        self.functions.push(loop_function);
+        Ok(Value::Undefined)
+    }
+
+    /// Game features a raw command relies on.
+    fn raw_features(code: &str) -> Vec<Feature> {
+        let mut features = Vec::new();
+        for line in code.lines().map(str::trim) {
+            if line.starts_with('$') {
+                features.push(Feature::Macros);
+            }
+            let words: Vec<&str> = line.trim_start_matches('$').split_whitespace().collect();
+            let returns = words.first() == Some(&"return")
+                || words
+                    .windows(2)
+                    .any(|pair| pair == ["run", "return"]);
+            if returns {
+                features.push(Feature::Return);
+            }
+        }
+        features
+    }
+
+    fn require(&self, feature: Feature, cursor: &Cursor) -> Result<(), Message> {
+        let target = &self.options.target;
+        if target.supports(feature) {
+            return Ok(());
+        }
+        Err(
+            Message::error(
+                UnsupportedFeature,
+                details::UnsupportedFeature!(feature.name(), feature.since(), target),
+                cursor.clone(),
+            ),
+        )
+    }
+
+    /// Compiles an expression whose value is discarded, recording its error
+    /// instead of stopping so that the following statements are still checked.
+    fn compile_statement(
+        &mut self,
+        scope: &mut Scope,
+        context: &Context,
+        expression: Expression,
+    ) {
+        // a variable whose declaration failed still exists, or every use of it
+        // would be reported too
+        let declared = match &expression {
+            | Expression::VariableDeclaration { name, cursor, .. } => Some((name.clone(), cursor.clone())),
+            | _ => None,
+        };
+
+        match self.compile_expression(
+            scope, context, expression,
+        ) {
+            | Ok(Value::Command(command)) => scope.function.push_cmd(command),
+            | Ok(_) => {}
+            | Err(error) => {
+                if let Some((name, cursor)) = declared {
+                    scope.symbol_table.entry(name).or_insert(cursor);
+                }
+                self.error(error);
+            }
+        }
+    }
+
+    /// Records an error, once even if the code is compiled again, like the
+    /// body of an unrolled loop.
+    fn error(&mut self, error: Message) {
+        let duplicate = self.errors.iter().any(|other| {
+            other.message_type.code() == error.message_type.code()
+                && other.cursor.start == error.cursor.start
+                && other.details == error.details
+        });
+        if !duplicate {
+            self.errors.push(error);
+        }
+    }
+
+    /// Reports what a lint found at the level it is configured to.
+    fn lint(&mut self, lint: Lint, message: Message) {
+        match self.options.lints.apply(lint, message) {
+            | Some(message) if message.is_error() => self.error(message),
+            | Some(message) => self.warnings.push(message),
+            | None => {}
+        }
+    }
+
     fn compile_function_call(&mut self, cursor: &Cursor, scope: &mut Scope, name: String) -> Result<Value, Message> {
-        if scope.symbol_table.contains(&name) {
-            scope.function.push_cmd(format!("function {}", &name));
+        // functions of other packs can't be checked
+        let location = match name.contains(':') {
+            | true => Some(ResourceLocation::parse(&name)),
+            | false => scope.function_location(&name).cloned(),
+        };
+        if let Some(location) = location {
+            scope.forget();
+            scope.function.push_cmd(format!("function {}", location));
             Ok(Value::Undefined)
         }
         else {
-            Err(Message::error(
-                UnknownMember,
-                details::UnknownMember!("function", &name),
-                cursor.clone()
-            ))
+            Err(unknown_member(scope, "function", &name, cursor))
         }
     }
 
@@ -601,13 +1259,41 @@ impl Compiler {
                 return_type,
                 body,
                 cursor,
-            } => self.compile_function_declaration(
-                cursor,
-                Some(scope),
-                context,
-                name,
-                body,
-            ),
+            } => {
+                let location = scope.location.child(&path_segment(&name));
+                let previous = scope
+                    .functions
+                    .iter()
+                    .find(|(other, other_location)| **other == name || **other_location == location)
+                    .and_then(|(other, other_location)| {
+                        Some((other, other_location, scope.symbol_table.get(other)?))
+                    });
+                if let Some((other, other_location, declaration)) = previous {
+                    let error = Message::error(
+                        MemberRedeclaration,
+                        details::MemberRedeclaration!("Function", name),
+                        cursor,
+                    );
+                    return Err(match (other_location == &location, other == &name) {
+                        | (false, _) => error.with_label(declaration.clone(), "imported here"),
+                        | (true, true) => error.with_label(declaration.clone(), "first declared here"),
+                        | (true, false) => error
+                            .with_label(declaration.clone(), "first declared here")
+                            .with_note(&format!(
+                                "'{}' and '{}' are both compiled to {}",
+                                other, name, location
+                            )),
+                    });
+                }
+                self.compile_function_declaration(
+                    cursor,
+                    Some(scope),
+                    context,
+                    name,
+                    location,
+                    body,
+                )
+            }
             | Expression::Integer(int, cursor) => self.compile_integer(
                 cursor, int,
             ),
@@ -643,28 +1329,155 @@ impl Compiler {
                 cursor, scope, context, *left, *right,
             ),
             | Expression::RawCode { string, cursor } => {
-                scope.function.push_cmd(string);
-                return Ok(Value::Undefined);
-            },
+                for feature in Self::raw_features(&string) {
+                    self.require(feature, &cursor)?;
+                }
+                scope.forget();
+                // only a single command has a result to store; macro lines
+                // can't follow `execute ... run`
+                let lines: Vec<&str> = string.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+                match lines.as_slice() {
+                    | [line] if !line.starts_with('$') => Ok(Value::Command(line.to_string())),
+                    | _ => {
+                        scope.function.push_cmd(string);
+                        Ok(Value::Undefined)
+                    }
+                }
+            }
+            | Expression::ForLoop {
+                variable,
+                start,
+                end,
+                body,
+                cursor,
+            } => self.compile_for_loop(
+                scope,
+                context,
+                ForLoop {
+                    variable,
+                    start: *start,
+                    end: *end,
+                    body,
+                    cursor,
+                },
+            ),
+            | Expression::Import { names, path, cursor } => {
+                for imported in names {
+                    let location = match self.imports.get(&imported.name) {
+                        | Some(location) => location.clone(),
+                        | None => {
+                            return Err(
+                                Message::error(
+                                    UnknownModule,
+                                    details::UnknownModule!(path),
+                                    cursor,
+                                ),
+                            )
+                        }
+                    };
+                    if let Some(declaration) = scope.symbol_table.get(&imported.name) {
+                        return Err(
+                            Message::error(
+                                MemberRedeclaration,
+                                details::MemberRedeclaration!("Function", imported.name),
+                                imported.cursor,
+                            )
+                            .with_label(declaration.clone(), "first declared here"),
+                        );
+                    }
+                    scope.symbol_table.insert(imported.name.clone(), imported.cursor);
+                    scope.functions.insert(imported.name, location);
+                }
+                Ok(Value::Undefined)
+            }
+            | Expression::Export { declaration, .. } => self.compile_expression(
+                scope, context, *declaration,
+            ),
             Expression::FunctionCall { arguments, cursor, name } => self.compile_function_call(&cursor, scope, name),
             | Expression::VariableAssign { name, value, cursor } => self.compile_variable_assign(cursor, scope, context, name, value),
-            | Expression::String( .. ) => unimplemented!()
+            | Expression::String(_, cursor) => Err(
+                Message::error(
+                    TypeMismatch,
+                    details::TypeMismatch!(
+                        "int",
+                        "string"
+                    ),
+                    cursor,
+                )
+                .with_note("strings can only be used in raw commands and imports"),
+            ),
+            // already reported by the parser
+            | Expression::ErrorNode(_) => Ok(Value::Undefined),
         }
     }
 }
 
-pub fn compile(ast: Vec<Expression>) -> Result<Vec<String>, Message> {
-    let mut compiler = Compiler::new();
-    compiler.compile_function_declaration(
-        Cursor::new("", ""),
+/// Compiles a program whose top-level code becomes the function `entry`.
+/// Functions it declares are nested below it. If anything fails, every error
+/// is returned along with the warnings, ordered by where they occur.
+pub fn compile(
+    ast: Vec<Expression>,
+    entry: &ResourceLocation,
+    options: Options,
+) -> Result<CompiledUnit, Vec<Message>> {
+    compile_with_imports(
+        ast,
+        entry,
+        Imports::new(),
+        options,
+    )
+}
+
+/// Compiles a module of a program like `compile`, calling the functions it
+/// imports where `imports` says they are compiled to.
+pub fn compile_with_imports(
+    ast: Vec<Expression>,
+    entry: &ResourceLocation,
+    imports: Imports,
+    options: Options,
+) -> Result<CompiledUnit, Vec<Message>> {
+    let mut compiler = Compiler::new(options, imports);
+    for (lint, message) in lint::check(&ast) {
+        compiler.lint(lint, message);
+    }
+    // the synthetic function spans nothing, at the start of the file
+    let file = ast
+        .first()
+        .map(|statement| statement.get_cursor().file)
+        .unwrap_or_default();
+    if let Err(error) = compiler.compile_function_declaration(
+        Cursor::new(file, 0..0),
         None,
         &Context { macro_target: None },
-        "main".to_string(),
+        entry.path.clone(),
+        entry.clone(),
         ast,
-    )?;
-    Ok(compiler
-        .functions
-        .iter()
-        .map(|fun| fun.to_string())
-        .collect())
+    ) {
+        compiler.error(error);
+    }
+
+    compiler
+        .warnings
+        .sort_by_key(|message| message.cursor.start);
+    if !compiler.errors.is_empty() {
+        let mut messages = compiler.errors;
+        messages.extend(compiler.warnings);
+        messages.sort_by_key(|message| message.cursor.start);
+        return Err(messages);
+    }
+    Ok(
+        CompiledUnit {
+            functions: compiler
+                .functions
+                .iter()
+                .map(|fun| Function {
+                    location: fun.location.clone(),
+                    body: fun.to_string(),
+                })
+                .collect(),
+            tags: Vec::new(),
+            resources: Vec::new(),
+            warnings: compiler.warnings,
+        },
+    )
 }
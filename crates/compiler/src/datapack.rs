@@ -0,0 +1,264 @@
+use std::{
+    fmt::{self, Display},
+    fs::{create_dir_all, read_dir, remove_dir_all, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use json::{object, JsonValue};
+use utilities::message::Message;
+
+use crate::{
+    stable_hash,
+    target::{Feature, Target},
+};
+
+const OBJECTIVE_LENGTH: usize = 16;
+
+/// Turns an identifier into a valid resource location path segment, `myFunction`
+/// becoming `my_function`.
+pub fn path_segment(identifier: &str) -> String {
+    let mut segment = String::new();
+    for (index, chr) in identifier.chars().enumerate() {
+        if chr.is_ascii_uppercase() {
+            if index > 0 {
+                segment.push('_');
+            }
+            segment.push(chr.to_ascii_lowercase());
+        } else {
+            segment.push(chr);
+        }
+    }
+    segment
+}
+
+/// A namespaced id such as `demo:util/helpers`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceLocation {
+    pub namespace: String,
+    pub path: String,
+}
+impl ResourceLocation {
+    pub fn new(namespace: impl ToString, path: impl ToString) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    /// Parses `namespace:path`, defaulting to the `minecraft` namespace.
+    pub fn parse(text: &str) -> Self {
+        match text.split_once(':') {
+            | Some((namespace, path)) => Self::new(namespace, path),
+            | None => Self::new("minecraft", text),
+        }
+    }
+
+    /// A location nested below this one, like a function declared inside another.
+    pub fn child(&self, segment: &str) -> Self {
+        Self::new(
+            &self.namespace,
+            format!(
+                "{}/{}",
+                self.path, segment
+            ),
+        )
+    }
+
+    /// A scoreboard objective name unique to this location. Objectives can't
+    /// contain `:` or `/` and are limited to 16 characters before 1.18.2, so
+    /// there long locations are shortened and disambiguated by a hash. Both
+    /// are replaced by `+`, which locations can't contain, so `a/b` and `a.b`
+    /// get different objectives.
+    pub fn objective(&self, target: &Target) -> String {
+        let name = format!(
+            "{}+{}",
+            self.namespace,
+            self.path.replace('/', "+")
+        );
+        if name.len() <= OBJECTIVE_LENGTH || target.supports(Feature::LongObjectives) {
+            return name;
+        }
+
+        let prefix: String = name.chars().take(OBJECTIVE_LENGTH - 9).collect();
+        format!(
+            "{}.{:08x}",
+            prefix,
+            stable_hash(&[&name]) as u32
+        )
+    }
+
+    /// Where a resource of the given kind lives, relative to the pack root.
+    pub fn file(&self, folder: &str, extension: &str) -> PathBuf {
+        let mut file = PathBuf::from("data");
+        file.push(&self.namespace);
+        file.push(folder);
+        for component in self.path.split('/') {
+            file.push(component);
+        }
+        file.set_extension(extension);
+        file
+    }
+}
+impl Display for ResourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.path)
+    }
+}
+
+#[derive(Clone)]
+pub struct Function {
+    pub location: ResourceLocation,
+    pub body: String,
+}
+
+/// A function tag, like `minecraft:tick`.
+#[derive(Clone)]
+pub struct Tag {
+    pub location: ResourceLocation,
+    pub values: Vec<ResourceLocation>,
+}
+impl Tag {
+    pub fn to_json(&self) -> JsonValue {
+        let values: Vec<String> = self
+            .values
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        object! { values: values }
+    }
+}
+
+/// Any other generated file, with a path relative to the pack root.
+#[derive(Clone)]
+pub struct Resource {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Everything produced by compiling a single source file.
+#[derive(Clone)]
+pub struct CompiledUnit {
+    pub functions: Vec<Function>,
+    pub tags: Vec<Tag>,
+    pub resources: Vec<Resource>,
+    pub warnings: Vec<Message>,
+}
+impl CompiledUnit {
+    pub fn function(&self, location: &ResourceLocation) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|function| &function.location == location)
+    }
+}
+
+pub struct Datapack {
+    pub description: String,
+    pub pack_format: i32,
+    /// `functions` before 1.21, `function` since
+    pub functions_folder: String,
+    pub functions: Vec<Function>,
+    pub tags: Vec<Tag>,
+    pub resources: Vec<Resource>,
+}
+impl Datapack {
+    pub fn new(description: impl ToString, target: &Target) -> Self {
+        Self {
+            description: description.to_string(),
+            pack_format: target.pack_format(),
+            functions_folder: target.functions_folder().to_string(),
+            functions: Vec::new(),
+            tags: Vec::new(),
+            resources: Vec::new(),
+        }
+    }
+
+    /// Merges a unit in, appending to tags that already exist.
+    pub fn add(&mut self, unit: CompiledUnit) {
+        self.functions.extend(unit.functions);
+        self.resources.extend(unit.resources);
+        for tag in unit.tags {
+            self.add_tag(tag);
+        }
+    }
+
+    pub fn add_tag(&mut self, tag: Tag) {
+        match self
+            .tags
+            .iter_mut()
+            .find(|other| other.location == tag.location)
+        {
+            | Some(other) => other.values.extend(tag.values),
+            | None => self.tags.push(tag),
+        }
+    }
+
+    pub fn mcmeta(&self) -> JsonValue {
+        object! {
+            pack: {
+                pack_format: self.pack_format,
+                description: self.description.as_str()
+            }
+        }
+    }
+
+    /// Every file of the pack along with its contents, relative to the pack root.
+    pub fn files(&self) -> Vec<(PathBuf, String)> {
+        let mut files = vec![(
+            PathBuf::from("pack.mcmeta"),
+            self.mcmeta().pretty(4),
+        )];
+        for function in &self.functions {
+            files.push((
+                function
+                    .location
+                    .file(&self.functions_folder, "mcfunction"),
+                function.body.clone(),
+            ));
+        }
+        let tags_folder = format!("tags/{}", self.functions_folder);
+        for tag in &self.tags {
+            files.push((
+                tag.location.file(&tags_folder, "json"),
+                tag.to_json().pretty(4),
+            ));
+        }
+        for resource in &self.resources {
+            files.push((
+                resource.path.clone(),
+                resource.contents.clone(),
+            ));
+        }
+        files
+    }
+
+    /// Writes the pack under `root`. The function and function tag folders of
+    /// every namespace there are deleted first, so functions that are no longer
+    /// generated or were laid out for another target don't linger.
+    pub fn write(&self, root: &Path) -> io::Result<()> {
+        let namespaces = match read_dir(root.join("data")) {
+            | Ok(namespaces) => namespaces,
+            | Err(error) if error.kind() == io::ErrorKind::NotFound => return self.write_files(root),
+            | Err(error) => return Err(error),
+        };
+        for namespace in namespaces {
+            let namespace = namespace?.path();
+            for folder in ["function", "functions", "tags/function", "tags/functions"] {
+                match remove_dir_all(namespace.join(folder)) {
+                    | Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    | _ => {}
+                }
+            }
+        }
+        self.write_files(root)
+    }
+
+    fn write_files(&self, root: &Path) -> io::Result<()> {
+        for (path, contents) in self.files() {
+            let path = root.join(path);
+            create_dir_all(path.parent().unwrap())?;
+            File::create(&path)?.write_all(contents.as_bytes())?;
+        }
+        Ok(())
+    }
+}
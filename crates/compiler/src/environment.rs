@@ -1,20 +1,43 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
+
+use utilities::cursor::Cursor;
+
+use crate::datapack::ResourceLocation;
+use crate::liveness::Liveness;
+use crate::target::Target;
 
 pub struct McFunction {
     text: String,
-    pub name: String,
+    pub location: ResourceLocation,
+    /// Scoreboard objective holding the function's locals
+    pub objective: String,
 }
 impl McFunction {
-    pub fn new(name: impl ToString) -> Self {
+    pub fn new(location: ResourceLocation, target: &Target) -> Self {
+        Self {
+            text: String::new(),
+            objective: location.objective(target),
+            location,
+        }
+    }
+
+    /// A function sharing the locals of another one, like a loop body.
+    pub fn with_objective(location: ResourceLocation, objective: impl ToString) -> Self {
         Self {
             text: String::new(),
-            name: name.to_string(),
+            location,
+            objective: objective.to_string(),
         }
     }
 
     pub fn push_cmd(&mut self, cmd: impl ToString) {
         self.text += &(cmd.to_string() + "\n");
     }
+
+    /// Adds the commands of another function, like a body compiled apart.
+    pub fn append(&mut self, other: &McFunction) {
+        self.text += &other.text;
+    }
 }
 impl ToString for McFunction {
     fn to_string(&self) -> String {
@@ -26,6 +49,8 @@ impl ToString for McFunction {
 pub enum Value {
     Int(i32),
     IntReference { scoreboard: String, player: String },
+    /// An int produced by running a command, stored wherever it is needed
+    Command(String),
     Undefined,
     UndefinedReference { scoreboard: String, player: String },
     FunctionReference(String),
@@ -34,26 +59,186 @@ pub enum Value {
 impl Value {
     pub fn name(&self) -> &'static str {
         match self {
-            | Value::Int(_) | Value::IntReference { .. } => "int",
+            | Value::Int(_) | Value::IntReference { .. } | Value::Command(_) => "int",
             | Value::Undefined | Value::UndefinedReference { .. } => "undefined",
             | Value::FunctionReference(_) => "function",
         }
     }
 }
 
+pub fn slot_player(slot: usize) -> String {
+    format!("${}", slot)
+}
+
+fn player_slot(player: &str) -> Option<usize> {
+    player.strip_prefix('$')?.parse().ok()
+}
+
+/// A math expression whose result is still held in a slot.
+struct Subexpression {
+    key: String,
+    slot: usize,
+    /// Players the result was computed from
+    inputs: Vec<String>,
+    /// The local the slot belonged to when the result was stored
+    owner: Option<String>,
+}
+
 pub struct Scope<'a> {
     pub function: &'a mut McFunction,
-    pub symbol_table: HashSet<String>,
+    /// Location of the declared function, which `function` may be a helper of
+    pub location: ResourceLocation,
+    /// Variables and functions, with where they are declared
+    pub symbol_table: HashMap<String, Cursor>,
+    /// Functions declared in this scope
+    pub functions: HashMap<String, ResourceLocation>,
+    /// Locals with a value known at compile time, like an unrolled loop variable
+    pub constants: HashMap<String, i32>,
     pub parent: Option<&'a Scope<'a>>,
+    liveness: Liveness,
+    statement: usize,
+    temps: Vec<usize>,
+    subexpressions: Vec<Subexpression>,
 }
 impl<'a> Scope<'a> {
-    pub fn new(function: &'a mut McFunction, parent: Option<&'a Scope<'a>>) -> Self {
+    pub fn new(
+        function: &'a mut McFunction,
+        liveness: Liveness,
+        parent: Option<&'a Scope<'a>>,
+    ) -> Self {
         Self {
+            location: function.location.clone(),
             function,
-            symbol_table: HashSet::new(),
+            symbol_table: HashMap::new(),
+            functions: HashMap::new(),
+            constants: HashMap::new(),
             parent,
+            liveness,
+            statement: 0,
+            temps: Vec::new(),
+            subexpressions: Vec::new(),
+        }
+    }
+
+    /// Finds a function declared here or in any enclosing scope.
+    pub fn function_location(&self, name: &str) -> Option<&ResourceLocation> {
+        match self.functions.get(name) {
+            | Some(location) => Some(location),
+            | None => self
+                .parent
+                .and_then(|parent| parent.function_location(name)),
         }
     }
+
+    /// Names that can be used here: the variables and functions of this scope,
+    /// and the functions of enclosing ones.
+    pub fn visible_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.symbol_table.keys().map(String::as_str).collect();
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            names.extend(current.functions.keys().map(String::as_str));
+            scope = current.parent;
+        }
+        names
+    }
+
+    /// Moves on to the next top-level statement, freeing every temporary that
+    /// doesn't hold a reusable result.
+    pub fn begin_statement(&mut self, statement: usize) {
+        self.statement = statement;
+
+        // a slot handed over to another local no longer holds the result
+        let liveness = &self.liveness;
+        self.subexpressions.retain(|subexpression| {
+            liveness.owner(subexpression.slot, statement) == subexpression.owner.as_deref()
+        });
+
+        let subexpressions = &self.subexpressions;
+        self.temps.retain(|&slot| {
+            subexpressions
+                .iter()
+                .any(|subexpression| subexpression.slot == slot)
+        });
+    }
+
+    /// The score holder a local variable is stored in.
+    pub fn local(&self, name: &str) -> String {
+        match self.liveness.slot(name) {
+            | Some(slot) => slot_player(slot),
+            | None => format!("${}", name),
+        }
+    }
+
+    /// Reserves a slot that is neither a live local nor another temporary.
+    pub fn acquire_temp(&mut self) -> String {
+        let mut slot = 0;
+        while self.temps.contains(&slot) || self.liveness.is_live(slot, self.statement) {
+            slot += 1;
+        }
+        self.temps.push(slot);
+        let player = slot_player(slot);
+        self.write(&player);
+        player
+    }
+
+    pub fn release_temp(&mut self, player: &str) {
+        if self.is_cached(player) {
+            return;
+        }
+        self.temps.retain(|&slot| slot_player(slot) != player);
+    }
+
+    pub fn is_temp(&self, player: &str) -> bool {
+        self.temps.iter().any(|&slot| slot_player(slot) == player)
+    }
+
+    /// The player already holding the result of a math expression.
+    pub fn cached(&self, key: &str) -> Option<String> {
+        self.subexpressions
+            .iter()
+            .find(|subexpression| subexpression.key == key)
+            .map(|subexpression| slot_player(subexpression.slot))
+    }
+
+    pub fn is_cached(&self, player: &str) -> bool {
+        self.subexpressions
+            .iter()
+            .any(|subexpression| slot_player(subexpression.slot) == player)
+    }
+
+    /// Remembers that `player` holds the result of the expression `key`.
+    pub fn cache(&mut self, key: String, player: &str, inputs: Vec<String>) {
+        let slot = match player_slot(player) {
+            | Some(slot) => slot,
+            | None => return,
+        };
+        if inputs.iter().any(|input| input == player) {
+            return;
+        }
+        let owner = self
+            .liveness
+            .owner(slot, self.statement)
+            .map(str::to_string);
+        self.subexpressions.push(Subexpression {
+            key,
+            slot,
+            inputs,
+            owner,
+        });
+    }
+
+    /// Forgets every result stored in or computed from `player`.
+    pub fn write(&mut self, player: &str) {
+        self.subexpressions.retain(|subexpression| {
+            slot_player(subexpression.slot) != player
+                && !subexpression.inputs.iter().any(|input| input == player)
+        });
+    }
+
+    /// Forgets every result, for code that may write any score.
+    pub fn forget(&mut self) {
+        self.subexpressions.clear();
+    }
 }
 
 #[derive(Clone)]
@@ -1,8 +1,667 @@
 pub mod compiler;
+pub mod datapack;
 mod environment;
+pub mod lint;
+mod liveness;
+pub mod module;
+pub mod target;
+
+/// Version of the compiler, as output of other versions can't be reused
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// FNV-1a of some text, which unlike the standard hasher stays the same across
+/// compiler builds.
+pub fn stable_hash(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        // separated so that moving text between parts changes the hash
+        for byte in part.bytes().chain([0xff]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
 
 #[cfg(test)]
 mod tests {
+    use super::compiler::{compile, compile_with_imports, Options};
+    use super::datapack::{CompiledUnit, Datapack, ResourceLocation, Tag};
+    use super::lint::{Level, Lint, Lints};
+    use super::module::{resolve_path, ModuleGraph};
+    use super::target::Target;
+    use parser::parser::parse;
+    use utilities::source::FileId;
+
+    fn parse_ok(text: &str) -> Vec<parser::ast::Expression> {
+        let (ast, messages) = parse(FileId::default(), text);
+        if let Some(message) = messages.first() {
+            panic!("{}", message.details);
+        }
+        ast
+    }
+
+    fn compile_unit(text: &str) -> CompiledUnit {
+        compile(parse_ok(text), &ResourceLocation::new("test", "main"), Options::default())
+            .map_err(|messages| {
+                messages
+                    .iter()
+                    .map(|message| message.details.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap()
+    }
+
+    fn compile_main(text: &str) -> String {
+        let unit = compile_unit(text);
+        unit.function(&ResourceLocation::new("test", "main"))
+            .unwrap()
+            .body
+            .clone()
+    }
+
+    #[test]
+    fn redeclared_locals_stay_live() {
+        let out = compile_main("let n = raw \"time query day\"\nfor i in 0..n {}\nlet a = 5\nfor i in 0..n {}\nlet b = a + 1");
+
+        // the second loop must not reuse the slot of `a`
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             execute store result score $0 test+main run time query day\n\
+             scoreboard players set $1 test+main 0\n\
+             scoreboard players operation $2 test+main = $0 test+main\n\
+             execute if score $1 test+main < $2 test+main run function test:main/loop-0\n\
+             scoreboard players set $2 test+main 5\n\
+             scoreboard players set $1 test+main 0\n\
+             scoreboard players operation $3 test+main = $0 test+main\n\
+             execute if score $1 test+main < $3 test+main run function test:main/loop-1\n\
+             scoreboard players operation $0 test+main = $2 test+main\n\
+             scoreboard players add $0 test+main 1\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn folding_leaves_overflow_and_division_by_zero_to_the_game() {
+        let out = compile_main("for i in 0..2 {\n    let x = 10 / i\n    raw \"say\"\n}\nlet y = 2147483647 + 1\nraw \"say\"");
+
+        assert!(out.contains("scoreboard players operation $1 test+main /= %0 test+main\n"), "{}", out);
+        assert!(out.contains("scoreboard players set $0 test+main 2147483647\nscoreboard players add $0 test+main 1\n"), "{}", out);
+    }
+
+    #[test]
+    fn disjoint_locals_share_a_slot() {
+        let out = compile_main("let a = 1\nlet b = a + 2\nlet c = b * 3");
+
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             scoreboard players set $0 test+main 1\n\
+             scoreboard players operation $1 test+main = $0 test+main\n\
+             scoreboard players add $1 test+main 2\n\
+             scoreboard players operation $0 test+main = $1 test+main\n\
+             scoreboard players set %3 test+main 3\n\
+             scoreboard players operation $0 test+main *= %3 test+main\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn temporaries_avoid_live_locals() {
+        let out = compile_main("let a = 1\nlet b = 2\nlet c = (a + b) * (b + a)");
+
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             scoreboard players set $0 test+main 1\n\
+             scoreboard players set $1 test+main 2\n\
+             scoreboard players operation $2 test+main = $0 test+main\n\
+             scoreboard players operation $2 test+main += $1 test+main\n\
+             scoreboard players operation $3 test+main = $1 test+main\n\
+             scoreboard players operation $3 test+main += $0 test+main\n\
+             scoreboard players operation $2 test+main *= $3 test+main\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn command_results_are_stored_in_place() {
+        let out = compile_main(
+            "let a = raw \"time query daytime\"\nlet b = (raw \"time query day\") * a\nraw \"say hi\"",
+        );
+
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             execute store result score $0 test+main run time query daytime\n\
+             execute store result score $1 test+main run time query day\n\
+             scoreboard players operation $1 test+main *= $0 test+main\n\
+             say hi\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn constant_loops_are_unrolled() {
+        let out = compile_main("for i in 0..3 {\n  raw \"say hi\"\n  let x = i * 2\n}");
+
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             say hi\n\
+             scoreboard players set $1 test+main 0\n\
+             say hi\n\
+             scoreboard players set $1 test+main 2\n\
+             say hi\n\
+             scoreboard players set $1 test+main 4\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn long_loops_recurse() {
+        let unit = compile_unit("for i in 0..100 {\n  raw \"say hi\"\n}");
+
+        assert_eq!(
+            unit.function(&ResourceLocation::new("test", "main/loop-0"))
+                .unwrap()
+                .body,
+            "say hi\n\
+             scoreboard players add $0 test+main 1\n\
+             execute if score $0 test+main matches ..99 run function test:main/loop-0\n"
+        );
+        assert_eq!(
+            unit.function(&ResourceLocation::new("test", "main"))
+                .unwrap()
+                .body,
+            "scoreboard objectives add test+main dummy\n\
+             scoreboard players set $0 test+main 0\n\
+             execute if score $0 test+main matches ..99 run function test:main/loop-0\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn generated_functions_keep_clear_of_declared_ones() {
+        let unit = compile_unit("function _loop0() {\n    raw \"say mine\"\n}\nlet n = raw \"time query day\"\nfor i in 0..n {\n    raw \"say loop\"\n}\n_loop0()");
+
+        let locations: Vec<String> = unit
+            .functions
+            .iter()
+            .map(|function| function.location.to_string())
+            .collect();
+        assert!(locations.contains(&"test:main/_loop0".to_string()), "{:?}", locations);
+        assert!(locations.contains(&"test:main/loop-0".to_string()), "{:?}", locations);
+    }
+
+    #[test]
+    fn tail_self_calls_reenter_the_body() {
+        let unit = compile_unit("function tick() {\n  raw \"say tick\"\n  tick()\n}");
+
+        assert_eq!(
+            unit.function(&ResourceLocation::new("test", "main/tick/tail-call"))
+                .unwrap()
+                .body,
+            "say tick\n\
+             function test:main/tick/tail-call\n"
+        );
+        assert_eq!(
+            unit.function(&ResourceLocation::new("test", "main/tick"))
+                .unwrap()
+                .body,
+            "scoreboard objectives add test+main+tick dummy\n\
+             function test:main/tick/tail-call\n\
+             scoreboard objectives remove test+main+tick\n"
+        );
+        assert_eq!(unit.warnings.len(), 1);
+        assert_eq!(unit.warnings[0].message_type.code(), "ES105W");
+    }
+
+    #[test]
+    fn only_unguarded_self_calls_warn() {
+        let unit = compile_unit("function f() {\n  f()\n  raw \"say after\"\n}");
+        assert_eq!(unit.warnings.len(), 1);
+        assert_eq!(unit.warnings[0].message_type.code(), "ES105W");
+
+        let options = Options {
+            target: Target::parse("1.20.4").unwrap(),
+            ..Options::default()
+        };
+        let unit = compile(
+            parse_ok("function countdown() {\n  raw \"execute if score #timer main matches ..0 run return 0\"\n  countdown()\n}"),
+            &ResourceLocation::new("test", "main"),
+            options,
+        )
+        .ok()
+        .unwrap();
+        assert!(unit.warnings.is_empty());
+        assert!(unit
+            .function(&ResourceLocation::new("test", "main/countdown/tail-call"))
+            .is_some());
+    }
+
+    #[test]
+    fn calls_to_a_nested_function_of_the_same_name_are_not_self_calls() {
+        let unit = compile_unit("function f() {\n  function f() {\n    raw \"say inner\"\n  }\n  f()\n}");
+
+        assert!(unit.warnings.is_empty());
+        assert!(unit
+            .function(&ResourceLocation::new("test", "main/f/tail-call"))
+            .is_none());
+        assert_eq!(
+            unit.function(&ResourceLocation::new("test", "main/f"))
+                .unwrap()
+                .body,
+            "scoreboard objectives add test+main+f dummy\n\
+             function test:main/f/f\n\
+             scoreboard objectives remove test+main+f\n"
+        );
+    }
+
+    #[test]
+    fn repeated_subexpressions_are_reused() {
+        let out = compile_main("let a = 1\nlet b = 2\nlet c = a + b\nlet d = (a + b) * 2\na = 4\nlet e = (a + b) + c + d");
+
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             scoreboard players set $0 test+main 1\n\
+             scoreboard players set $1 test+main 2\n\
+             scoreboard players operation $2 test+main = $0 test+main\n\
+             scoreboard players operation $2 test+main += $1 test+main\n\
+             scoreboard players operation $3 test+main = $2 test+main\n\
+             scoreboard players set %2 test+main 2\n\
+             scoreboard players operation $3 test+main *= %2 test+main\n\
+             scoreboard players set $0 test+main 4\n\
+             scoreboard players operation $4 test+main = $0 test+main\n\
+             scoreboard players operation $4 test+main += $1 test+main\n\
+             scoreboard players operation $4 test+main += $2 test+main\n\
+             scoreboard players operation $4 test+main += $3 test+main\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn targets_read_on_the_right_go_through_a_temporary() {
+        let out = compile_main("let b = 2\nb = 3 - b");
+
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             scoreboard players set $0 test+main 2\n\
+             scoreboard players set $1 test+main 3\n\
+             scoreboard players operation $1 test+main -= $0 test+main\n\
+             scoreboard players operation $0 test+main = $1 test+main\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn functions_are_namespaced_by_path() {
+        let unit = compile_unit("function greetPlayer() {\n  raw \"say hi\"\n}\ngreetPlayer()\nminecraft:some/other_fn()");
+
+        assert!(unit
+            .function(&ResourceLocation::new("test", "main/greet_player"))
+            .is_some());
+        assert_eq!(
+            unit.function(&ResourceLocation::new("test", "main"))
+                .unwrap()
+                .body,
+            "scoreboard objectives add test+main dummy\n\
+             function test:main/greet_player\n\
+             function minecraft:some/other_fn\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn long_objectives_are_hashed() {
+        let old = Target::parse("1.18.1").unwrap();
+        let new = Target::parse("1.18.2").unwrap();
+        let short = ResourceLocation::new("demo", "main");
+        let long = ResourceLocation::new("demo", "util/some_long_name");
+
+        assert_eq!(short.objective(&old), "demo+main");
+        assert_eq!(long.objective(&old).len(), 16);
+        assert_ne!(
+            long.objective(&old),
+            ResourceLocation::new("demo", "util/some_long_other").objective(&old)
+        );
+        assert_eq!(long.objective(&new), "demo+util+some_long_name");
+    }
+
+    #[test]
+    fn targets_select_the_pack_layout() {
+        let target = Target::parse("1.20.4").unwrap();
+        assert_eq!(target.pack_format(), 26);
+        assert_eq!(target.functions_folder(), "functions");
+
+        let target = Target::parse("1.21").unwrap();
+        assert_eq!(target.pack_format(), 48);
+        assert_eq!(target.functions_folder(), "function");
+
+        assert!(Target::parse("1.12.2").is_err());
+        assert!(Target::parse("latest").is_err());
+    }
+
+    #[test]
+    fn unavailable_features_are_errors() {
+        let compile_for = |target: &str, text: &str| {
+            compile(
+                parse_ok(text),
+                &ResourceLocation::new("test", "main"),
+                Options {
+                    target: Target::parse(target).unwrap(),
+                    ..Options::default()
+                },
+            )
+            .err()
+        };
+
+        let errors = compile_for("1.19.4", "raw \"execute if entity @p run return 1\"").unwrap();
+        assert_eq!(errors[0].message_type.code(), "ES106E");
+        assert!(compile_for("1.20", "raw \"execute if entity @p run return 1\"").is_none());
+        assert!(compile_for("1.20.1", "raw \"$say $(text)\"").is_some());
+        assert!(compile_for("1.20.2", "raw \"$say $(text)\"").is_none());
+    }
+
+    #[test]
+    fn every_semantic_error_is_reported() {
+        let errors = compile(
+            parse_ok("let a = b\nlet c = a + d\nraw \"say hi\""),
+            &ResourceLocation::new("test", "main"),
+            Options::default(),
+        )
+        .err()
+        .unwrap();
+
+        let errors: Vec<&str> = errors
+            .iter()
+            .filter(|message| message.is_error())
+            .map(|message| message.details.as_str())
+            .collect();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn values_that_are_not_ints_are_mismatched() {
+        for (text, column) in [
+            ("let a = \"hi\"", 8),
+            ("let a = 1\nlet b = a + \"x\"", 22),
+            ("function f() {\n    raw \"say hi\"\n}\nlet a = 1 + f()", 46),
+        ] {
+            let errors = compile(parse_ok(text), &ResourceLocation::new("test", "main"), Options::default())
+                .err()
+                .unwrap();
+            let error = errors
+                .iter()
+                .find(|message| message.is_error())
+                .unwrap();
+            assert_eq!(error.message_type.code(), "ES102E", "{}", text);
+            assert_eq!(error.cursor.start, column, "{}", text);
+        }
+    }
+
+    #[test]
+    fn only_single_commands_are_raw_values() {
+        let options = Options {
+            target: Target::parse("1.20.2").unwrap(),
+            ..Options::default()
+        };
+        for text in [
+            "let a = raw \"say a\nsay b\"",
+            "let a = raw \"$scoreboard players get $(name) main\"",
+        ] {
+            let errors = compile(parse_ok(text), &ResourceLocation::new("test", "main"), options.clone())
+                .err()
+                .unwrap();
+            let error = errors
+                .iter()
+                .find(|message| message.is_error())
+                .unwrap();
+            assert_eq!(error.message_type.code(), "ES102E", "{}", text);
+        }
+
+        let out = compile_main("raw \"say a\nsay b\"");
+        assert_eq!(
+            out,
+            "scoreboard objectives add test+main dummy\n\
+             say a\nsay b\n\
+             scoreboard objectives remove test+main\n"
+        );
+    }
+
+    #[test]
+    fn redeclarations_point_at_the_first_declaration() {
+        let errors = compile(
+            parse_ok("let a = 1\nraw \"say\"\nlet a = 2"),
+            &ResourceLocation::new("test", "main"),
+            Options::default(),
+        )
+        .err()
+        .unwrap();
+
+        let error = errors
+            .iter()
+            .find(|message| message.is_error())
+            .unwrap();
+        assert_eq!(error.message_type.code(), "ES104E");
+        assert_eq!(error.cursor.start, "let a = 1\nraw \"say\"\n".len());
+        assert_eq!(error.labels[0].cursor.start, 0);
+
+        let errors = compile(
+            parse_ok("function myFn() {\n    raw \"say a\"\n}\nfunction my_fn() {\n    raw \"say b\"\n}"),
+            &ResourceLocation::new("test", "main"),
+            Options::default(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(errors[0].message_type.code(), "ES104E");
+        assert_eq!(errors[0].notes, ["'myFn' and 'my_fn' are both compiled to test:main/my_fn"]);
+    }
+
+    #[test]
+    fn unknown_names_suggest_similar_ones() {
+        let errors = compile(
+            parse_ok("let count = 1\nfunction greet() {\n    raw \"say hi\"\n}\nfunction main() {\n    let total = 2\n    total = cuont\n    greeet()\n    lett = 3\n    xyz = 4\n}"),
+            &ResourceLocation::new("test", "main"),
+            Options::default(),
+        )
+        .err()
+        .unwrap();
+
+        let help: Vec<Vec<String>> = errors
+            .iter()
+            .filter(|message| message.is_error())
+            .map(|message| message.help.clone())
+            .collect();
+        // variables of the enclosing function aren't visible, its functions are
+        assert_eq!(
+            help,
+            vec![
+                vec![],
+                vec!["did you mean 'greet'?".to_string()],
+                vec!["did you mean 'let'?".to_string()],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn lints_report_likely_mistakes() {
+        let codes = |text: &str| -> Vec<&'static str> {
+            super::lint::check(&parse_ok(text))
+                .iter()
+                .map(|(_, message)| message.message_type.code())
+                .collect()
+        };
+
+        assert_eq!(codes("let a = 1\nlet _b = 2"), vec!["ES107W"]);
+        assert_eq!(
+            codes("function outer() {\n    function inner() {\n        raw \"say\"\n    }\n}\nouter()"),
+            vec!["ES108W"]
+        );
+        assert_eq!(
+            codes("let i = 0\nfor i in 0..3 {\n    let b = i\n    b = b\n}\ni = i"),
+            vec!["ES109W"]
+        );
+        // variables of enclosing functions aren't visible, so nothing is hidden
+        assert!(codes("let a = 1\nfunction f() {\n    let a = 2\n    raw \"say\"\n    let b = a\n    b = b\n}\nf()\nlet c = a\nc = c").is_empty());
+        assert_eq!(codes("let a: int\nlet b = a + a\na = b"), vec!["ES110W"]);
+        assert_eq!(
+            codes("raw \"return 1\"\nraw \"say\"\nfor i in 3..1 {\n    raw \"say\"\n}"),
+            vec!["ES111W", "ES107W", "ES111W"]
+        );
+    }
+
+    #[test]
+    fn lint_levels_are_applied() {
+        let compile_with = |level: Level| {
+            let mut lints = Lints::default();
+            lints.set(Lint::UnusedVariable, level);
+            compile(
+                parse_ok("let a = 1"),
+                &ResourceLocation::new("test", "main"),
+                Options {
+                    lints,
+                    ..Options::default()
+                },
+            )
+        };
+
+        assert!(compile_with(Level::Allow).ok().unwrap().warnings.is_empty());
+        assert_eq!(compile_with(Level::Warn).ok().unwrap().warnings.len(), 1);
+        let errors = compile_with(Level::Deny).err().unwrap();
+        assert!(errors[0].is_error());
+        assert_eq!(errors[0].message_type.code(), "ES107W");
+    }
+
+    #[test]
+    fn datapack_lays_out_files() {
+        let mut datapack = Datapack::new("demo", &Target::default());
+        let mut unit = compile_unit("raw \"say hi\"");
+        unit.tags.push(Tag {
+            location: ResourceLocation::parse("minecraft:load"),
+            values: vec![ResourceLocation::new("test", "main")],
+        });
+        datapack.add(unit);
+
+        let files: Vec<String> = datapack
+            .files()
+            .iter()
+            .map(|(path, _)| path.to_string_lossy().replace('\\', "/"))
+            .collect();
+
+        assert_eq!(
+            files,
+            vec![
+                "pack.mcmeta",
+                "data/test/functions/main.mcfunction",
+                "data/minecraft/tags/functions/load.json",
+            ]
+        );
+    }
+
+    #[test]
+    fn datapacks_replace_stale_functions() {
+        let root = std::env::temp_dir().join(format!("es-datapack-{}", std::process::id()));
+        let mut datapack = Datapack::new("demo", &Target::default());
+        datapack.add(compile_unit("function greet() {\n    raw \"say hi\"\n}"));
+        datapack.write(&root).unwrap();
+        assert!(root.join("data/test/functions/main/greet.mcfunction").exists());
+
+        let mut datapack = Datapack::new("demo", &Target::parse("1.21").unwrap());
+        datapack.add(compile_unit("raw \"say hi\""));
+        datapack.write(&root).unwrap();
+        let exists = root.join("data/test/functions").exists();
+        let main = root.join("data/test/function/main.mcfunction").exists();
+        let greet = root.join("data/test/function/main/greet.mcfunction").exists();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!exists);
+        assert!(main);
+        assert!(!greet);
+    }
+
+    #[test]
+    fn import_paths_stay_in_the_namespace() {
+        let from = ResourceLocation::new("demo", "lib/main");
+
+        assert_eq!(resolve_path(&from, "./util"), Some(ResourceLocation::new("demo", "lib/util")));
+        assert_eq!(resolve_path(&from, "../shared/math.es"), Some(ResourceLocation::new("demo", "shared/math")));
+        assert_eq!(resolve_path(&from, "../../util"), None);
+        assert_eq!(resolve_path(&from, "util"), None);
+    }
+
+    #[test]
+    fn modules_resolve_imports_and_report_cycles() {
+        let main = parse_ok("import { greet, shout, gret } from \"./util\"\nimport { x } from \"./missing\"\ngreet()");
+        let util = parse_ok("import { tick } from \"./loop\"\nexport function greet() {\n    raw \"say hi\"\n}\nfunction shout() {\n    raw \"say HI\"\n}");
+        let cycle = parse_ok("import { greet } from \"./util\"\nexport function tick() {\n    greet()\n}");
+        let main_location = ResourceLocation::new("demo", "main");
+        let util_location = ResourceLocation::new("demo", "util");
+        let loop_location = ResourceLocation::new("demo", "loop");
+        let modules = ModuleGraph::new([
+            (main_location.clone(), main.as_slice()),
+            (util_location.clone(), util.as_slice()),
+            (loop_location.clone(), cycle.as_slice()),
+        ]);
+
+        let errors: Vec<(&str, &[String])> = modules
+            .messages(&main_location)
+            .iter()
+            .map(|message| (message.message_type.code(), message.help.as_slice()))
+            .collect();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0], ("ES202E", &["'shout' is declared there, but isn't exported".to_string()][..]));
+        assert_eq!(errors[1], ("ES202E", &["did you mean 'greet'?".to_string()][..]));
+        assert_eq!(errors[2].0, "ES200E");
+
+        // the cycle is reported once, where it closes
+        let cycles: Vec<&str> = [&util_location, &loop_location]
+            .into_iter()
+            .flat_map(|location| modules.messages(location))
+            .map(|message| message.details.as_str())
+            .collect();
+        assert_eq!(cycles, ["Modules import each other in a cycle: demo:loop -> demo:util -> demo:loop"]);
+
+        let mut dependents = modules.dependents(&util_location);
+        dependents.sort();
+        assert_eq!(dependents, [&loop_location, &main_location, &util_location]);
+
+        let unit = compile_with_imports(
+            main,
+            &main_location,
+            modules.imports(&main_location),
+            Options::default(),
+        )
+        .map_err(|messages| messages[0].details.clone())
+        .unwrap();
+        assert!(unit.functions[0].body.contains("function demo:util/greet"));
+
+        // imports are declarations like any other
+        for text in [
+            "import { greet, greet } from \"./util\"",
+            "import { greet } from \"./util\"\nfunction greet() {\n    raw \"say hi\"\n}",
+        ] {
+            let errors = compile_with_imports(
+                parse_ok(text),
+                &main_location,
+                modules.imports(&main_location),
+                Options::default(),
+            )
+            .err()
+            .unwrap();
+            let error = errors
+                .iter()
+                .find(|message| message.is_error())
+                .unwrap();
+            assert_eq!(error.message_type.code(), "ES104E", "{}", text);
+            assert_eq!(error.labels[0].cursor.start, "import { ".len(), "{}", text);
+        }
+    }
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
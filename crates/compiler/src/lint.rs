@@ -0,0 +1,421 @@
+use std::{collections::BTreeMap, fmt};
+
+use parser::ast::Expression;
+use utilities::message::{
+    details, Message,
+    MessageType::{self, *},
+    Severity,
+};
+
+/// A check for code that compiles but is likely a mistake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedFunction,
+    Shadowing,
+    UndefinedRead,
+    UnreachableCode,
+    UnboundedRecursion,
+}
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::UnusedVariable,
+        Lint::UnusedFunction,
+        Lint::Shadowing,
+        Lint::UndefinedRead,
+        Lint::UnreachableCode,
+        Lint::UnboundedRecursion,
+    ];
+
+    /// Name of the lint in esconfig.json.
+    pub fn name(&self) -> &'static str {
+        match self {
+            | Lint::UnusedVariable => "unused-variable",
+            | Lint::UnusedFunction => "unused-function",
+            | Lint::Shadowing => "shadowing",
+            | Lint::UndefinedRead => "undefined-read",
+            | Lint::UnreachableCode => "unreachable-code",
+            | Lint::UnboundedRecursion => "unbounded-recursion",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|lint| lint.name() == name)
+    }
+
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            | Lint::UnusedVariable => UnusedVariable,
+            | Lint::UnusedFunction => UnusedFunction,
+            | Lint::Shadowing => Shadowing,
+            | Lint::UndefinedRead => UndefinedRead,
+            | Lint::UnreachableCode => UnreachableCode,
+            | Lint::UnboundedRecursion => UnboundedRecursion,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    /// Reported as an error, failing the build
+    Deny,
+}
+impl Level {
+    pub const ALL: [Level; 3] = [Level::Allow, Level::Warn, Level::Deny];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            | Level::Allow => "allow",
+            | Level::Warn => "warn",
+            | Level::Deny => "deny",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name() == name)
+    }
+}
+
+/// The level of every lint, which is `Warn` unless set otherwise.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lints {
+    levels: BTreeMap<Lint, Level>,
+}
+impl Lints {
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels
+            .get(&lint)
+            .copied()
+            .unwrap_or(Level::Warn)
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    /// The message of a lint at its level, `None` if it is allowed.
+    pub fn apply(&self, lint: Lint, message: Message) -> Option<Message> {
+        let severity = match self.level(lint) {
+            | Level::Allow => return None,
+            | Level::Warn => Severity::Warning,
+            | Level::Deny => Severity::Error,
+        };
+        Some(Message { severity, ..message })
+    }
+}
+impl fmt::Display for Lints {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let levels: Vec<String> = Lint::ALL
+            .iter()
+            .map(|lint| {
+                format!(
+                    "{}={}",
+                    lint.name(),
+                    self.level(*lint).name()
+                )
+            })
+            .collect();
+        write!(f, "{}", levels.join(","))
+    }
+}
+
+struct Variable {
+    name: String,
+    declaration: Expression,
+    read: bool,
+    /// Whether it surely holds a value, or was already reported as read without one
+    defined: bool,
+}
+
+struct Function {
+    name: String,
+    declaration: Expression,
+    called: bool,
+}
+
+/// Names declared by a function body or a loop body.
+struct Block {
+    is_function: bool,
+    /// Whether functions declared here can be called from outside the file
+    is_public: bool,
+    variables: Vec<Variable>,
+    functions: Vec<Function>,
+}
+
+struct Checker {
+    blocks: Vec<Block>,
+    found: Vec<(Lint, Message)>,
+}
+impl Checker {
+    fn report(&mut self, lint: Lint, details: &str, expression: &Expression) {
+        self.report_with_label(lint, details, expression, None);
+    }
+
+    /// Reports a lint, pointing at a related declaration too.
+    fn report_with_label(
+        &mut self,
+        lint: Lint,
+        details: &str,
+        expression: &Expression,
+        label: Option<(&Expression, &str)>,
+    ) {
+        let mut message = Message::warning(
+            lint.message_type(),
+            details,
+            expression.get_cursor().clone(),
+        );
+        if let Some((declaration, text)) = label {
+            message = message.with_label(declaration.get_cursor().clone(), text);
+        }
+        self.found.push((lint, message));
+    }
+
+    /// Blocks whose variables are visible from the current one. Variables of
+    /// enclosing functions aren't.
+    fn visible(&mut self) -> impl Iterator<Item = &mut Block> {
+        let function = self
+            .blocks
+            .iter()
+            .rposition(|block| block.is_function)
+            .unwrap_or(0);
+        self.blocks[function..].iter_mut().rev()
+    }
+
+    fn variable(&mut self, name: &str) -> Option<&mut Variable> {
+        self.visible()
+            .flat_map(|block| block.variables.iter_mut().rev())
+            .find(|variable| variable.name == name)
+    }
+
+    /// Declares a variable in the innermost block, reporting it if it hides
+    /// one of an enclosing block of the same function.
+    fn declare(&mut self, name: &str, defined: bool, declaration: &Expression) {
+        let function = self
+            .blocks
+            .iter()
+            .rposition(|block| block.is_function)
+            .unwrap_or(0);
+        let current = self.blocks.len() - 1;
+        let shadowed = self.blocks[function..current]
+            .iter()
+            .rev()
+            .flat_map(|block| block.variables.iter().rev())
+            .find(|variable| variable.name == name)
+            .map(|variable| variable.declaration.clone());
+        if let Some(shadowed) = shadowed {
+            self.report_with_label(
+                Lint::Shadowing,
+                details::Shadowing!(name),
+                declaration,
+                Some((&shadowed, "the hidden variable is declared here")),
+            );
+        }
+
+        self.blocks.last_mut().unwrap().variables.push(Variable {
+            name: name.to_string(),
+            declaration: declaration.clone(),
+            read: false,
+            defined,
+        });
+    }
+
+    fn enter(&mut self, is_function: bool) {
+        let is_public = self.blocks.is_empty()
+            || (!is_function && self.blocks.iter().filter(|block| block.is_function).count() == 1);
+        self.blocks.push(Block {
+            is_function,
+            is_public,
+            variables: Vec::new(),
+            functions: Vec::new(),
+        });
+    }
+
+    /// Reports what the innermost block declared but never used.
+    fn leave(&mut self) {
+        let block = self.blocks.pop().unwrap();
+        for variable in block.variables {
+            if !variable.read && !variable.name.starts_with('_') {
+                self.report(
+                    Lint::UnusedVariable,
+                    details::UnusedVariable!(variable.name),
+                    &variable.declaration,
+                );
+            }
+        }
+        if !block.is_public {
+            for function in block.functions {
+                if !function.called {
+                    self.report(
+                        Lint::UnusedFunction,
+                        details::UnusedFunction!(function.name),
+                        &function.declaration,
+                    );
+                }
+            }
+        }
+    }
+
+    fn body(&mut self, body: &[Expression]) {
+        let mut unreachable = None;
+        for expression in body {
+            if let Some(reason) = unreachable.take() {
+                self.report(
+                    Lint::UnreachableCode,
+                    details::UnreachableCode!(reason),
+                    expression,
+                );
+            }
+            self.visit(expression);
+            if returns(expression) {
+                unreachable = Some("the function returns before it");
+            }
+        }
+    }
+
+    fn visit(&mut self, expression: &Expression) {
+        match expression {
+            | Expression::VariableDeclaration { name, value, .. } => {
+                if let Some(value) = value {
+                    self.visit(value);
+                }
+                self.declare(name, value.is_some(), expression);
+            }
+            | Expression::VariableAssign { name, value, .. } => {
+                self.visit(value);
+                if let Some(variable) = self.variable(name) {
+                    variable.defined = true;
+                }
+            }
+            | Expression::VariableAccess(name, _) => {
+                let undefined = match self.variable(name) {
+                    | Some(variable) => {
+                        variable.read = true;
+                        match std::mem::replace(&mut variable.defined, true) {
+                            | true => None,
+                            | false => Some(variable.declaration.clone()),
+                        }
+                    }
+                    | None => None,
+                };
+                if let Some(declaration) = undefined {
+                    self.report_with_label(
+                        Lint::UndefinedRead,
+                        details::UndefinedRead!(name),
+                        expression,
+                        Some((&declaration, "declared here without a value")),
+                    );
+                }
+            }
+            | Expression::Addition { left, right, .. }
+            | Expression::Subtraction { left, right, .. }
+            | Expression::Multiplication { left, right, .. }
+            | Expression::Division { left, right, .. } => {
+                self.visit(left);
+                self.visit(right);
+            }
+            | Expression::ForLoop {
+                variable,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.visit(start);
+                self.visit(end);
+                if let (Expression::Integer(start, _), Expression::Integer(end, _), Some(first)) =
+                    (start.as_ref(), end.as_ref(), body.first())
+                {
+                    if end <= start {
+                        self.report(
+                            Lint::UnreachableCode,
+                            details::UnreachableCode!("the loop never iterates"),
+                            first,
+                        );
+                    }
+                }
+                self.enter(false);
+                self.declare(variable, true, expression);
+                self.body(body);
+                self.leave();
+            }
+            | Expression::FunctionDeclaration { name, body, .. } => {
+                self.blocks.last_mut().unwrap().functions.push(Function {
+                    name: name.clone(),
+                    declaration: expression.clone(),
+                    called: false,
+                });
+                self.enter(true);
+                self.body(body);
+                self.leave();
+            }
+            | Expression::FunctionCall { name, arguments, .. } => {
+                for argument in arguments {
+                    self.visit(&argument.expression);
+                }
+                let function = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .flat_map(|block| block.functions.iter_mut().rev())
+                    .find(|function| &function.name == name);
+                if let Some(function) = function {
+                    function.called = true;
+                }
+            }
+            | Expression::Export { declaration, .. } => self.visit(declaration),
+            | Expression::Import { .. }
+            | Expression::RawCode { .. }
+            | Expression::String(..)
+            | Expression::Integer(..)
+            | Expression::ErrorNode(..) => {}
+        }
+    }
+}
+
+/// Whether a statement always returns from the function, like a raw `return`.
+fn returns(expression: &Expression) -> bool {
+    match expression {
+        | Expression::RawCode { string, .. } => string.lines().any(|line| {
+            line.trim()
+                .trim_start_matches('$')
+                .split_whitespace()
+                .next()
+                == Some("return")
+        }),
+        | _ => false,
+    }
+}
+
+/// Whether a statement may return from the function, like a raw
+/// `execute if ... run return`.
+pub(crate) fn guards(expression: &Expression) -> bool {
+    match expression {
+        | Expression::RawCode { string, .. } => string.lines().any(|line| {
+            let line = line.trim().trim_start_matches('$');
+            line.starts_with("execute ") && line.contains(" run return")
+        }),
+        | _ => returns(expression),
+    }
+}
+
+/// Runs every lint over the body of a file, returning what they found as
+/// warnings.
+pub fn check(ast: &[Expression]) -> Vec<(Lint, Message)> {
+    let mut checker = Checker {
+        blocks: Vec::new(),
+        found: Vec::new(),
+    };
+    checker.enter(true);
+    checker.body(ast);
+    checker.leave();
+    checker
+        .found
+        .sort_by_key(|(_, message)| message.cursor.start);
+    checker.found
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use parser::ast::Expression;
+
+/// Range of top-level statements (inclusive) during which a local variable
+/// holds a value that may still be read.
+struct Interval {
+    name: String,
+    start: usize,
+    end: usize,
+    slot: usize,
+}
+
+/// Score slot assignment for the locals of a single function body.
+///
+/// Locals whose live ranges don't overlap share a slot, and temporaries are
+/// handed out from slots that no live local occupies at the given statement.
+pub struct Liveness {
+    intervals: Vec<Interval>,
+    slots: HashMap<String, usize>,
+}
+impl Liveness {
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.slots.get(name).copied()
+    }
+
+    pub fn is_live(&self, slot: usize, statement: usize) -> bool {
+        self.owner(slot, statement).is_some()
+    }
+
+    /// The local occupying a slot at the given statement.
+    pub fn owner(&self, slot: usize, statement: usize) -> Option<&str> {
+        self.intervals
+            .iter()
+            .find(|interval| {
+                interval.slot == slot && interval.start <= statement && statement <= interval.end
+            })
+            .map(|interval| interval.name.as_str())
+    }
+}
+
+/// Starts the interval of a local, or extends it when the name is declared
+/// again, as a name keeps its slot across declarations.
+fn declare(intervals: &mut Vec<Interval>, name: &str, statement: usize) {
+    if let Some(interval) = intervals.iter_mut().find(|interval| interval.name == name) {
+        interval.end = statement;
+        return;
+    }
+    intervals.push(Interval {
+        name: name.to_string(),
+        start: statement,
+        end: statement,
+        slot: 0,
+    });
+}
+
+fn touch(intervals: &mut [Interval], name: &str, statement: usize) {
+    if let Some(interval) = intervals.iter_mut().find(|interval| interval.name == name) {
+        interval.end = statement;
+    }
+}
+
+fn visit(intervals: &mut Vec<Interval>, expression: &Expression, statement: usize) {
+    match expression {
+        | Expression::VariableDeclaration { name, value, .. } => {
+            declare(intervals, name, statement);
+            if let Some(value) = value {
+                visit(intervals, value, statement);
+            }
+        }
+        | Expression::VariableAssign { name, value, .. } => {
+            touch(intervals, name, statement);
+            visit(intervals, value, statement);
+        }
+        | Expression::VariableAccess(name, _) => touch(intervals, name, statement),
+        | Expression::Addition { left, right, .. }
+        | Expression::Subtraction { left, right, .. }
+        | Expression::Multiplication { left, right, .. }
+        | Expression::Division { left, right, .. } => {
+            visit(intervals, left, statement);
+            visit(intervals, right, statement);
+        }
+        | Expression::ForLoop {
+            variable,
+            start,
+            end,
+            body,
+            ..
+        } => {
+            visit(intervals, start, statement);
+            visit(intervals, end, statement);
+            declare(intervals, variable, statement);
+            for expression in body {
+                visit(intervals, expression, statement);
+            }
+        }
+        | Expression::FunctionCall { arguments, .. } => {
+            for argument in arguments {
+                visit(intervals, &argument.expression, statement);
+            }
+        }
+        // nested functions live on their own objective
+        | Expression::FunctionDeclaration { .. }
+        | Expression::Export { .. }
+        | Expression::Import { .. }
+        | Expression::RawCode { .. }
+        | Expression::String(..)
+        | Expression::Integer(..)
+        | Expression::ErrorNode(..) => {}
+    }
+}
+
+pub fn analyse(body: &[Expression]) -> Liveness {
+    let mut intervals: Vec<Interval> = Vec::new();
+
+    for (statement, expression) in body.iter().enumerate() {
+        visit(&mut intervals, expression, statement);
+    }
+
+    // intervals are already ordered by their start
+    let mut active: Vec<usize> = Vec::new();
+    for index in 0..intervals.len() {
+        let start = intervals[index].start;
+        active.retain(|&other| intervals[other].end >= start);
+
+        let mut slot = 0;
+        while active.iter().any(|&other| intervals[other].slot == slot) {
+            slot += 1;
+        }
+
+        intervals[index].slot = slot;
+        active.push(index);
+    }
+
+    let slots = intervals
+        .iter()
+        .map(|interval| (interval.name.clone(), interval.slot))
+        .collect();
+
+    Liveness { intervals, slots }
+}
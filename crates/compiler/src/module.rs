@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use parser::ast::{Expression, ImportedName};
+use utilities::{
+    cursor::Cursor,
+    message::{details, Message, MessageType::*},
+    suggest::{did_you_mean, similar},
+};
+
+use crate::datapack::{path_segment, ResourceLocation};
+
+/// Functions a module imports, by name, with where they are compiled to.
+pub type Imports = HashMap<String, ResourceLocation>;
+
+/// The module an import path leads to from the module at `from`. Paths are
+/// relative to the importing module, may end with `.es` and can't leave the
+/// namespace.
+pub fn resolve_path(from: &ResourceLocation, path: &str) -> Option<ResourceLocation> {
+    if !path.starts_with("./") && !path.starts_with("../") {
+        return None;
+    }
+    let mut segments: Vec<&str> = from.path.split('/').collect();
+    segments.pop();
+    for segment in path.strip_suffix(".es").unwrap_or(path).split('/') {
+        match segment {
+            | "." => {}
+            | ".." => {
+                segments.pop()?;
+            }
+            | "" => return None,
+            | segment => segments.push(segment),
+        }
+    }
+    Some(ResourceLocation::new(
+        &from.namespace,
+        segments.join("/"),
+    ))
+}
+
+struct Import {
+    path: String,
+    /// Module the path leads to, if it stays in the namespace
+    module: Option<ResourceLocation>,
+    names: Vec<ImportedName>,
+    cursor: Cursor,
+}
+
+struct Module {
+    /// Functions declared at the top level, and whether they are exported
+    functions: HashMap<String, bool>,
+    imports: Vec<Import>,
+    /// Errors of the imports
+    messages: Vec<Message>,
+}
+impl Module {
+    fn new(location: &ResourceLocation, ast: &[Expression]) -> Self {
+        let mut functions = HashMap::new();
+        let mut imports = Vec::new();
+        for expression in ast {
+            match expression {
+                | Expression::FunctionDeclaration { name, .. } => {
+                    functions.insert(name.clone(), false);
+                }
+                | Expression::Export { declaration, .. } => {
+                    if let Expression::FunctionDeclaration { name, .. } = declaration.as_ref() {
+                        functions.insert(name.clone(), true);
+                    }
+                }
+                | Expression::Import { names, path, cursor } => imports.push(Import {
+                    path: path.clone(),
+                    module: resolve_path(location, path),
+                    names: names.clone(),
+                    cursor: cursor.clone(),
+                }),
+                | _ => {}
+            }
+        }
+        Self {
+            functions,
+            imports,
+            messages: Vec::new(),
+        }
+    }
+
+    fn exports(&self) -> impl Iterator<Item = &str> {
+        self.functions
+            .iter()
+            .filter(|(_, exported)| **exported)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// How the modules of a program import each other. Every source file is a
+/// module, known by the location its top-level code compiles to.
+#[derive(Default)]
+pub struct ModuleGraph {
+    modules: BTreeMap<ResourceLocation, Module>,
+}
+impl ModuleGraph {
+    /// Builds the graph of parsed modules, reporting imports that can't be
+    /// resolved and modules importing each other in a cycle.
+    pub fn new<'a>(modules: impl IntoIterator<Item = (ResourceLocation, &'a [Expression])>) -> Self {
+        let mut graph = Self::default();
+        for (location, ast) in modules {
+            let module = Module::new(&location, ast);
+            graph.modules.insert(location, module);
+        }
+        graph.check_imports();
+        graph.check_cycles();
+        graph
+    }
+
+    /// Functions the module at `location` imports. Names that can't be
+    /// resolved are mapped as if they could, as they are already reported.
+    pub fn imports(&self, location: &ResourceLocation) -> Imports {
+        let mut imports = Imports::new();
+        for import in self.modules.get(location).into_iter().flat_map(|module| &module.imports) {
+            let module = import.module.as_ref().unwrap_or(location);
+            for name in &import.names {
+                imports.insert(
+                    name.name.clone(),
+                    module.child(&path_segment(&name.name)),
+                );
+            }
+        }
+        imports
+    }
+
+    /// Errors of the imports of the module at `location`.
+    pub fn messages(&self, location: &ResourceLocation) -> &[Message] {
+        self.modules
+            .get(location)
+            .map_or(&[], |module| &module.messages)
+    }
+
+    /// Modules importing the module at `location`, directly or through others.
+    pub fn dependents(&self, location: &ResourceLocation) -> Vec<&ResourceLocation> {
+        let mut dependents: Vec<&ResourceLocation> = Vec::new();
+        let mut queue = vec![location];
+        while let Some(current) = queue.pop() {
+            for (other, module) in &self.modules {
+                let imports = module
+                    .imports
+                    .iter()
+                    .any(|import| import.module.as_ref() == Some(current));
+                if imports && !dependents.contains(&other) {
+                    dependents.push(other);
+                    queue.push(other);
+                }
+            }
+        }
+        dependents
+    }
+
+    fn check_imports(&mut self) {
+        let mut messages = Vec::new();
+        for (location, module) in &self.modules {
+            for import in &module.imports {
+                let found = import
+                    .module
+                    .as_ref()
+                    .and_then(|path| Some((path, self.modules.get(path)?)));
+                let (path, imported) = match found {
+                    | Some(found) => found,
+                    | None => {
+                        let error = Message::error(
+                            UnknownModule,
+                            details::UnknownModule!(import.path),
+                            import.cursor.clone(),
+                        );
+                        let error = match import.path.starts_with('.') {
+                            | true => error,
+                            | false => error.with_help(&format!(
+                                "paths are relative to the importing file, like \"./{}\"",
+                                import.path
+                            )),
+                        };
+                        messages.push((location.clone(), error));
+                        continue;
+                    }
+                };
+
+                for name in &import.names {
+                    let error = match imported.functions.get(&name.name) {
+                        | Some(true) => continue,
+                        | Some(false) => Message::error(
+                            UnknownExport,
+                            details::UnknownExport!(path, name.name),
+                            name.cursor.clone(),
+                        )
+                        .with_help(&format!(
+                            "'{}' is declared there, but isn't exported",
+                            name.name
+                        )),
+                        | None => {
+                            let error = Message::error(
+                                UnknownExport,
+                                details::UnknownExport!(path, name.name),
+                                name.cursor.clone(),
+                            );
+                            match did_you_mean(&similar(&name.name, imported.exports())) {
+                                | Some(help) => error.with_help(&help),
+                                | None => error,
+                            }
+                        }
+                    };
+                    messages.push((location.clone(), error));
+                }
+            }
+        }
+        for (location, message) in messages {
+            self.modules
+                .get_mut(&location)
+                .unwrap()
+                .messages
+                .push(message);
+        }
+    }
+
+    fn check_cycles(&mut self) {
+        let mut finished = HashSet::new();
+        let mut cycles = Vec::new();
+        for location in self.modules.keys() {
+            self.visit(location, &mut Vec::new(), &mut finished, &mut cycles);
+        }
+        for (location, message) in cycles {
+            self.modules
+                .get_mut(&location)
+                .unwrap()
+                .messages
+                .push(message);
+        }
+    }
+
+    /// Walks the imports of a module depth first, reporting every import that
+    /// leads back to a module of `path`.
+    fn visit<'a>(
+        &'a self,
+        location: &'a ResourceLocation,
+        path: &mut Vec<&'a ResourceLocation>,
+        finished: &mut HashSet<&'a ResourceLocation>,
+        cycles: &mut Vec<(ResourceLocation, Message)>,
+    ) {
+        if finished.contains(location) {
+            return;
+        }
+        path.push(location);
+        for import in &self.modules[location].imports {
+            let module = match &import.module {
+                | Some(module) if self.modules.contains_key(module) => module,
+                | _ => continue,
+            };
+            match path.iter().position(|visited| *visited == module) {
+                | Some(start) => {
+                    let cycle: Vec<String> = path[start..]
+                        .iter()
+                        .chain([&module])
+                        .map(|location| location.to_string())
+                        .collect();
+                    cycles.push((
+                        location.clone(),
+                        Message::error(
+                            ImportCycle,
+                            details::ImportCycle!(cycle.join(" -> ")),
+                            import.cursor.clone(),
+                        ),
+                    ));
+                }
+                | None => self.visit(module, path, finished, cycles),
+            }
+        }
+        path.pop();
+        finished.insert(location);
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::{self, Display};
+
+/// A Minecraft release, like `1.20.4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u32, pub u32, pub u32);
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.trim().split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = match parts.next() {
+            | Some(patch) => patch.parse().ok()?,
+            | None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self(major, minor, patch))
+    }
+}
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.2 {
+            | 0 => write!(f, "{}.{}", self.0, self.1),
+            | patch => write!(f, "{}.{}.{}", self.0, self.1, patch),
+        }
+    }
+}
+
+/// First release of each data pack format, newest last.
+const PACK_FORMATS: &[(Version, i32)] = &[
+    (Version(1, 13, 0), 4),
+    (Version(1, 15, 0), 5),
+    (Version(1, 16, 2), 6),
+    (Version(1, 17, 0), 7),
+    (Version(1, 18, 0), 8),
+    (Version(1, 18, 2), 9),
+    (Version(1, 19, 0), 10),
+    (Version(1, 19, 4), 12),
+    (Version(1, 20, 0), 15),
+    (Version(1, 20, 2), 18),
+    (Version(1, 20, 3), 26),
+    (Version(1, 20, 5), 41),
+    (Version(1, 21, 0), 48),
+    (Version(1, 21, 2), 57),
+    (Version(1, 21, 4), 61),
+    (Version(1, 21, 5), 71),
+    (Version(1, 21, 6), 80),
+    (Version(1, 21, 7), 81),
+];
+
+/// Latest release known to share the format of the last `PACK_FORMATS` entry
+const LATEST: Version = Version(1, 21, 8);
+
+/// Something only some versions of the game understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Objective names longer than 16 characters
+    LongObjectives,
+    /// The `return` command
+    Return,
+    /// Function macro lines, starting with `$`
+    Macros,
+}
+impl Feature {
+    pub fn since(&self) -> Version {
+        match self {
+            | Feature::LongObjectives => Version(1, 18, 2),
+            | Feature::Return => Version(1, 20, 0),
+            | Feature::Macros => Version(1, 20, 2),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            | Feature::LongObjectives => "Objective names over 16 characters",
+            | Feature::Return => "The return command",
+            | Feature::Macros => "Macro lines",
+        }
+    }
+}
+
+/// The Minecraft version a pack is compiled for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub version: Version,
+}
+impl Target {
+    /// Parses a version like `1.20.4`, failing for ones without data packs
+    /// or newer than this compiler knows about.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let version = Version::parse(text).ok_or_else(|| {
+            format!(
+                "Invalid target \"{}\", expected a version like 1.20.4",
+                text
+            )
+        })?;
+        if version < PACK_FORMATS[0].0 || version > LATEST {
+            return Err(format!(
+                "Unsupported target {}, versions from {} to {} are supported",
+                version, PACK_FORMATS[0].0, LATEST
+            ));
+        }
+        Ok(Self { version })
+    }
+
+    pub fn pack_format(&self) -> i32 {
+        PACK_FORMATS
+            .iter()
+            .rev()
+            .find(|(since, _)| *since <= self.version)
+            .map(|(_, format)| *format)
+            .unwrap()
+    }
+
+    /// `functions` before 1.21, `function` since.
+    pub fn functions_folder(&self) -> &'static str {
+        match self.version < Version(1, 21, 0) {
+            | true => "functions",
+            | false => "function",
+        }
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.version >= feature.since()
+    }
+}
+impl Default for Target {
+    fn default() -> Self {
+        Self {
+            version: Version(1, 19, 0),
+        }
+    }
+}
+impl Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.version.fmt(f)
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, read_dir},
+    path::{Path, PathBuf},
+};
+
+use compiler::{
+    compiler::{compile_with_imports, Options},
+    datapack::{CompiledUnit, Datapack, ResourceLocation, Tag},
+    module::ModuleGraph,
+    target::Target,
+};
+use parser::program::Program;
+use utilities::{
+    message::Message,
+    source::{FileId, SourceMap},
+};
+
+use crate::{
+    cache::Cache,
+    config::{is_valid_namespace, namespace_from, Config},
+    report::MessageFormat,
+};
+
+/// The config of a project along with the overrides given on the command line.
+pub struct Settings {
+    pub config: Config,
+    pub source_path: PathBuf,
+    pub output_path: PathBuf,
+    /// Folder of compiled units kept between builds, next to the config
+    pub cache_path: PathBuf,
+    pub target: Target,
+}
+impl Settings {
+    /// How every file of the project is compiled.
+    pub fn options(&self) -> Options {
+        Options {
+            target: self.target,
+            lints: self.config.lints.clone(),
+            unroll_limit: self.config.unroll_limit,
+        }
+    }
+
+    pub fn load(
+        config_path: &Path,
+        source_path: Option<&str>,
+        output_path: Option<&str>,
+        target: Option<&str>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let config = Config::load(config_path)?;
+        let target = match target {
+            | Some(target) => Target::parse(target)?,
+            | None => config.target,
+        };
+        Ok(Self {
+            source_path: PathBuf::from(source_path.unwrap_or(&config.source_folder)),
+            output_path: PathBuf::from(output_path.unwrap_or(&config.output_folder)).join(&config.name),
+            cache_path: config_path
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join(".esc-cache"),
+            target,
+            config,
+        })
+    }
+}
+
+/// A source file, whose top-level code becomes the function `<namespace>:<path>`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Entry {
+    pub namespace: String,
+    /// Folder of the namespace
+    pub root: PathBuf,
+    /// Path of the file relative to `root`
+    pub path: PathBuf,
+}
+impl Entry {
+    pub fn file(&self) -> PathBuf {
+        self.root.join(&self.path)
+    }
+
+    pub fn location(&self) -> ResourceLocation {
+        let components: Vec<String> = self
+            .path
+            .with_extension("")
+            .iter()
+            .map(|component| component.to_string_lossy().to_string())
+            .collect();
+        ResourceLocation::new(
+            &self.namespace,
+            components.join("/"),
+        )
+    }
+
+    /// Checks that the namespace and every folder and file name can be used
+    /// in a function id.
+    pub fn check_names(&self) -> Result<(), String> {
+        let path = self.path.with_extension("");
+        let names = path
+            .iter()
+            .map(|component| component.to_string_lossy().to_string());
+        for name in [self.namespace.clone()].into_iter().chain(names) {
+            if !is_valid_namespace(&name) {
+                return Err(format!(
+                    "{}: '{}' can't be used in a function id, which may only contain a-z, 0-9, '_', '-' and '.'; rename it to '{}'",
+                    self.file().display(),
+                    name,
+                    namespace_from(&name)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a source file couldn't be compiled.
+pub enum EntryError {
+    Io(String),
+    /// A file or folder named so that it can't become a function id
+    InvalidName(String),
+    /// Every error found in the file, along with its warnings
+    Diagnostics(Vec<Message>),
+}
+impl EntryError {
+    pub fn errors(&self) -> usize {
+        match self {
+            | EntryError::Io(_) | EntryError::InvalidName(_) => 1,
+            | EntryError::Diagnostics(messages) => messages
+                .iter()
+                .filter(|message| message.is_error())
+                .count(),
+        }
+    }
+
+    pub fn warnings(&self) -> usize {
+        match self {
+            | EntryError::Io(_) | EntryError::InvalidName(_) => 0,
+            | EntryError::Diagnostics(messages) => messages.len() - self.errors(),
+        }
+    }
+
+    pub fn report(&self, format: MessageFormat, sources: &SourceMap) {
+        match self {
+            | EntryError::Io(error) | EntryError::InvalidName(error) => format.status(error),
+            | EntryError::Diagnostics(messages) => format.messages(messages, sources),
+        }
+    }
+}
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            | EntryError::Io(error) | EntryError::InvalidName(error) => write!(f, "{}", error),
+            | EntryError::Diagnostics(_) => write!(
+                f,
+                "{} error(s), {} warning(s)",
+                self.errors(),
+                self.warnings()
+            ),
+        }
+    }
+}
+
+/// Reads a source file into the program, which parses it under its path.
+pub fn load_entry(entry: &Entry, program: &mut Program) -> Result<FileId, EntryError> {
+    entry.check_names().map_err(EntryError::InvalidName)?;
+    let text = fs::read_to_string(entry.file()).map_err(|error| EntryError::Io(error.to_string()))?;
+    Ok(program.add(entry.file().to_string_lossy(), text))
+}
+
+/// Compiles a parsed file of the program, or takes it from the cache if it
+/// didn't change. Syntax errors fail it, but what could be parsed is still
+/// compiled so that its other errors are reported too.
+pub fn compile_module(
+    program: &Program,
+    modules: &ModuleGraph,
+    file: FileId,
+    location: &ResourceLocation,
+    options: &Options,
+    cache: Option<&mut Cache>,
+) -> Result<CompiledUnit, EntryError> {
+    // whether imports resolve depends on other files, so units with import
+    // errors aren't taken from the cache
+    let cache = cache.filter(|_| modules.messages(location).is_empty());
+    let key = Cache::key(location, options, program.sources.text(file));
+    match cache {
+        | Some(cache) => match cache.get(&key, file) {
+            | Some(unit) => Ok(unit),
+            | None => {
+                let unit = compile_parsed(program, modules, file, location, options)?;
+                cache.put(&key, &unit);
+                Ok(unit)
+            }
+        },
+        | None => compile_parsed(program, modules, file, location, options),
+    }
+}
+
+fn compile_parsed(
+    program: &Program,
+    modules: &ModuleGraph,
+    file: FileId,
+    location: &ResourceLocation,
+    options: &Options,
+) -> Result<CompiledUnit, EntryError> {
+    let module = program
+        .module(file)
+        .expect("files are parsed when added to the program");
+    let mut messages = module.messages.clone();
+    messages.extend_from_slice(modules.messages(location));
+    let result = compile_with_imports(
+        module.ast.clone(),
+        location,
+        modules.imports(location),
+        options.clone(),
+    );
+
+    match result {
+        | Ok(unit) if messages.is_empty() => Ok(unit),
+        | Ok(unit) => {
+            messages.extend(unit.warnings);
+            messages.sort_by_key(|message| message.cursor.start);
+            Err(EntryError::Diagnostics(messages))
+        }
+        | Err(diagnostics) => {
+            messages.extend(diagnostics);
+            messages.sort_by_key(|message| message.cursor.start);
+            Err(EntryError::Diagnostics(messages))
+        }
+    }
+}
+
+/// Reads every entry into one program. Entries that couldn't be read are
+/// reported and left out, and the number of errors is returned along.
+pub fn load_program(entries: &[Entry], format: MessageFormat) -> (Program, Vec<(Entry, FileId)>, usize) {
+    let mut program = Program::new();
+    let mut loaded = Vec::new();
+    let mut errors = 0;
+    for entry in entries {
+        match load_entry(entry, &mut program) {
+            | Ok(file) => loaded.push((entry.clone(), file)),
+            | Err(error) => {
+                error.report(format, &program.sources);
+                errors += error.errors();
+            }
+        }
+    }
+    (program, loaded, errors)
+}
+
+/// How the loaded entries import each other.
+pub fn module_graph(program: &Program, loaded: &[(Entry, FileId)]) -> ModuleGraph {
+    ModuleGraph::new(loaded.iter().filter_map(|(entry, file)| {
+        let module = program.module(*file)?;
+        Some((entry.location(), module.ast.as_slice()))
+    }))
+}
+
+/// Collects the `.es` files below `dir`, relative to `root`.
+fn find_sources(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_sources(root, &path, out)?;
+        } else if path.extension().is_some_and(|extension| extension == "es") {
+            out.push(path.strip_prefix(root)?.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Every source file of the project. Folders of the source folder are namespaces,
+/// while files directly in it belong to the project's namespace.
+pub fn find_entries(settings: &Settings) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    for dir_entry in read_dir(&settings.source_path)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+
+        if dir_entry.metadata()?.is_dir() {
+            let mut sources = Vec::new();
+            find_sources(&path, &path, &mut sources)?;
+            for source in sources {
+                entries.push(Entry {
+                    namespace: dir_entry.file_name().to_string_lossy().to_string(),
+                    root: path.clone(),
+                    path: source,
+                });
+            }
+        } else if path.extension().is_some_and(|extension| extension == "es") {
+            entries.push(Entry {
+                namespace: settings.config.namespace.clone(),
+                root: settings.source_path.clone(),
+                path: PathBuf::from(dir_entry.file_name()),
+            });
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// Puts compiled units together into a pack, along with the project's tags.
+pub fn assemble<'a>(settings: &Settings, units: impl IntoIterator<Item = &'a CompiledUnit>) -> Datapack {
+    let config = &settings.config;
+    let mut datapack = Datapack::new(&config.name, &settings.target);
+    for unit in units {
+        datapack.add(unit.clone());
+    }
+
+    for (tag, functions) in [("load", &config.load), ("tick", &config.tick)] {
+        if !functions.is_empty() {
+            datapack.add_tag(Tag {
+                location: ResourceLocation::new("minecraft", tag),
+                values: functions
+                    .iter()
+                    .map(|function| config.function(function))
+                    .collect(),
+            });
+        }
+    }
+    datapack
+}
+
+pub fn build(
+    config_path: Option<&str>,
+    source_path: Option<&str>,
+    output_path: Option<&str>,
+    target: Option<&str>,
+    use_cache: bool,
+    format: MessageFormat,
+) -> Result<bool, Box<dyn Error>> {
+    let settings = Settings::load(
+        Path::new(config_path.unwrap_or("./esconfig.json")),
+        source_path,
+        output_path,
+        target,
+    )?;
+
+    let mut cache = use_cache.then(|| Cache::new(&settings.cache_path));
+
+    let options = settings.options();
+    let (program, loaded, errors) = load_program(&find_entries(&settings)?, format);
+    let modules = module_graph(&program, &loaded);
+    let mut units = Vec::new();
+    let mut success = errors == 0;
+    for (entry, file) in loaded {
+        match compile_module(&program, &modules, file, &entry.location(), &options, cache.as_mut()) {
+            | Ok(unit) => {
+                format.messages(&unit.warnings, &program.sources);
+                units.push(unit);
+            }
+            | Err(error) => {
+                error.report(format, &program.sources);
+                success = false;
+            }
+        }
+    }
+
+    if success {
+        assemble(&settings, &units).write(&settings.output_path)?;
+        // units of files that failed are kept for when they're fixed
+        if let Some(cache) = cache {
+            cache.prune();
+        }
+    }
+
+    Ok(success)
+}
+
+/// Compiles every file of the project without writing anything, returning
+/// whether there were no errors.
+pub fn check(
+    config_path: Option<&str>,
+    source_path: Option<&str>,
+    target: Option<&str>,
+    format: MessageFormat,
+) -> Result<bool, Box<dyn Error>> {
+    let settings = Settings::load(
+        Path::new(config_path.unwrap_or("./esconfig.json")),
+        source_path,
+        None,
+        target,
+    )?;
+
+    let options = settings.options();
+    let (program, loaded, mut errors) = load_program(&find_entries(&settings)?, format);
+    let modules = module_graph(&program, &loaded);
+    let mut warnings = 0;
+    for (entry, file) in loaded {
+        match compile_module(&program, &modules, file, &entry.location(), &options, None) {
+            | Ok(unit) => {
+                format.messages(&unit.warnings, &program.sources);
+                warnings += unit.warnings.len();
+            }
+            | Err(error) => {
+                error.report(format, &program.sources);
+                errors += error.errors();
+                warnings += error.warnings();
+            }
+        }
+    }
+
+    format.status(format!(
+        "Checked {}: {} error(s), {} warning(s)",
+        settings.config.name, errors, warnings
+    ));
+    Ok(errors == 0)
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use compiler::{
+    compiler::Options,
+    datapack::{CompiledUnit, Function, Resource, ResourceLocation, Tag},
+    stable_hash, VERSION,
+};
+use json::{object, JsonValue};
+use utilities::{
+    cursor::Cursor,
+    message::{Message, MessageType},
+    source::FileId,
+};
+
+/// Version of what is stored for a unit, so units stored before a change to
+/// it aren't read back
+const FORMAT: &str = "2";
+
+/// Compiled units stored on disk, so unchanged files aren't compiled again.
+/// Each unit is keyed by everything its compilation depends on, so entries
+/// never have to be invalidated, only pruned once unused.
+pub struct Cache {
+    dir: PathBuf,
+    used: HashSet<String>,
+}
+impl Cache {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            used: HashSet::new(),
+        }
+    }
+
+    pub fn key(location: &ResourceLocation, options: &Options, text: &str) -> String {
+        format!(
+            "{:016x}",
+            stable_hash(&[
+                VERSION,
+                FORMAT,
+                &options.target.to_string(),
+                &options.lints.to_string(),
+                &options.unroll_limit.to_string(),
+                &location.to_string(),
+                text,
+            ])
+        )
+    }
+
+    fn file(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension("json")
+    }
+
+    /// The unit stored for `key`, which was compiled from `file`.
+    pub fn get(&mut self, key: &str, file: FileId) -> Option<CompiledUnit> {
+        let contents = fs::read_to_string(self.file(key)).ok()?;
+        let unit = unit_from_json(&json::parse(&contents).ok()?, file)?;
+        self.used.insert(key.to_string());
+        Some(unit)
+    }
+
+    /// Stores a unit. The cache is only an optimisation, so failing to write
+    /// it isn't an error.
+    pub fn put(&mut self, key: &str, unit: &CompiledUnit) {
+        if fs::create_dir_all(&self.dir).is_ok() {
+            let _ = fs::write(self.file(key), unit_to_json(unit).dump());
+        }
+        self.used.insert(key.to_string());
+    }
+
+    /// Removes every unit that wasn't used since the cache was opened.
+    pub fn prune(&self) {
+        let entries = match fs::read_dir(&self.dir) {
+            | Ok(entries) => entries,
+            | Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let used = path
+                .file_stem()
+                .is_some_and(|key| self.used.contains(key.to_string_lossy().as_ref()));
+            if !used && path.extension().is_some_and(|extension| extension == "json") {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+fn unit_to_json(unit: &CompiledUnit) -> JsonValue {
+    let functions: Vec<JsonValue> = unit
+        .functions
+        .iter()
+        .map(|function| {
+            object! {
+                location: function.location.to_string(),
+                body: function.body.as_str()
+            }
+        })
+        .collect();
+    let tags: Vec<JsonValue> = unit
+        .tags
+        .iter()
+        .map(|tag| {
+            object! {
+                location: tag.location.to_string(),
+                values: tag.to_json()["values"].clone()
+            }
+        })
+        .collect();
+    let resources: Vec<JsonValue> = unit
+        .resources
+        .iter()
+        .map(|resource| {
+            object! {
+                path: resource.path.to_string_lossy().to_string(),
+                contents: resource.contents.as_str()
+            }
+        })
+        .collect();
+    let warnings: Vec<JsonValue> = unit
+        .warnings
+        .iter()
+        .map(|warning| {
+            let labels: Vec<JsonValue> = warning
+                .labels
+                .iter()
+                .map(|label| {
+                    object! {
+                        start: label.cursor.start,
+                        end: label.cursor.end,
+                        text: label.text.as_str()
+                    }
+                })
+                .collect();
+            object! {
+                code: warning.message_type.code(),
+                details: warning.details.as_str(),
+                start: warning.cursor.start,
+                end: warning.cursor.end,
+                labels: labels,
+                notes: warning.notes.clone(),
+                help: warning.help.clone()
+            }
+        })
+        .collect();
+
+    object! {
+        functions: functions,
+        tags: tags,
+        resources: resources,
+        warnings: warnings
+    }
+}
+
+/// Reads a unit back, pointing its warnings into `file`. Anything malformed
+/// counts as a cache miss.
+fn unit_from_json(json: &JsonValue, file: FileId) -> Option<CompiledUnit> {
+    let mut unit = CompiledUnit {
+        functions: Vec::new(),
+        tags: Vec::new(),
+        resources: Vec::new(),
+        warnings: Vec::new(),
+    };
+    for function in json["functions"].members() {
+        unit.functions.push(Function {
+            location: ResourceLocation::parse(function["location"].as_str()?),
+            body: function["body"].as_str()?.to_string(),
+        });
+    }
+    for tag in json["tags"].members() {
+        let values: Option<Vec<ResourceLocation>> = tag["values"]
+            .members()
+            .map(|value| value.as_str().map(ResourceLocation::parse))
+            .collect();
+        unit.tags.push(Tag {
+            location: ResourceLocation::parse(tag["location"].as_str()?),
+            values: values?,
+        });
+    }
+    for resource in json["resources"].members() {
+        unit.resources.push(Resource {
+            path: PathBuf::from(resource["path"].as_str()?),
+            contents: resource["contents"].as_str()?.to_string(),
+        });
+    }
+    for warning in json["warnings"].members() {
+        let cursor = Cursor::new(file, warning["start"].as_usize()?..warning["end"].as_usize()?);
+        let mut message = Message::warning(
+            MessageType::from_code(warning["code"].as_str()?)?,
+            warning["details"].as_str()?,
+            cursor,
+        );
+        for label in warning["labels"].members() {
+            let cursor = Cursor::new(file, label["start"].as_usize()?..label["end"].as_usize()?);
+            message = message.with_label(cursor, label["text"].as_str()?);
+        }
+        for note in warning["notes"].members() {
+            message = message.with_note(note.as_str()?);
+        }
+        for help in warning["help"].members() {
+            message = message.with_help(help.as_str()?);
+        }
+        unit.warnings.push(message);
+    }
+    Some(unit)
+}
@@ -1,7 +1,362 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs,
+    path::Path,
+};
+
+use compiler::{
+    compiler::Options,
+    datapack::ResourceLocation,
+    lint::{Level, Lint, Lints},
+    target::Target,
+};
+use json::{object, JsonValue};
+
+/// JSON schema of esconfig.json, for editor completion and validation
+pub const SCHEMA: &str = include_str!("../esconfig.schema.json");
+
+const KEYS: &[&str] = &["$schema", "name", "namespace", "source", "output", "target", "load", "tick", "lints", "unrollLimit"];
+
 pub struct Config {
     pub name: String,
     pub namespace: String,
     pub source_folder: String,
     pub output_folder: String,
-    pub gen_file_struct: bool
-}
\ No newline at end of file
+    pub target: Target,
+    /// Functions run when the pack is loaded
+    pub load: Vec<String>,
+    /// Functions run every tick
+    pub tick: Vec<String>,
+    pub lints: Lints,
+    /// Longest constant loop that is unrolled
+    pub unroll_limit: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with one key of esconfig.json.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub key: String,
+    pub text: String,
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            | Severity::Error => "error",
+            | Severity::Warning => "warning",
+        };
+        write!(f, "{}: esconfig.json: \"{}\": {}", severity, self.key, self.text)
+    }
+}
+
+/// Every error found in a config file that couldn't be loaded.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<Diagnostic>);
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.0.iter().map(|diagnostic| diagnostic.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+impl Error for ConfigError {}
+
+/// Whether a name can be used as a resource location namespace.
+pub fn is_valid_namespace(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|chr| matches!(chr, 'a'..='z' | '0'..='9' | '_' | '-' | '.'))
+}
+
+/// A valid namespace resembling `name`, like `my_pack` for "My Pack".
+pub fn namespace_from(name: &str) -> String {
+    let namespace: String = name
+        .trim()
+        .chars()
+        .map(|chr| match chr.to_ascii_lowercase() {
+            | chr @ ('a'..='z' | '0'..='9' | '_' | '-' | '.') => chr,
+            | _ => '_',
+        })
+        .collect();
+    match namespace.is_empty() {
+        | true => "minecraft".to_string(),
+        | false => namespace,
+    }
+}
+
+impl Config {
+    pub fn new(name: impl ToString) -> Self {
+        let name = name.to_string();
+        Self {
+            namespace: namespace_from(&name),
+            name,
+            source_folder: "./src".to_string(),
+            output_folder: "./out".to_string(),
+            target: Target::default(),
+            load: Vec::new(),
+            tick: Vec::new(),
+            lints: Lints::default(),
+            unroll_limit: Options::default().unroll_limit,
+        }
+    }
+
+    /// A function id from the config, which is in the project's namespace
+    /// unless it names another one.
+    pub fn function(&self, id: &str) -> ResourceLocation {
+        match id.contains(':') {
+            | true => ResourceLocation::parse(id),
+            | false => ResourceLocation::new(&self.namespace, id),
+        }
+    }
+
+    /// Reads a config, falling back to `default_name` for the pack name. Unknown
+    /// keys are reported as warnings alongside the config, anything invalid fails.
+    pub fn parse(text: &str, default_name: &str) -> Result<(Self, Vec<Diagnostic>), ConfigError> {
+        let json = json::parse(text).map_err(|error| {
+            ConfigError(vec![Diagnostic {
+                severity: Severity::Error,
+                key: "$".to_string(),
+                text: error.to_string(),
+            }])
+        })?;
+        if !json.is_object() {
+            return Err(ConfigError(vec![Diagnostic {
+                severity: Severity::Error,
+                key: "$".to_string(),
+                text: "expected an object".to_string(),
+            }]));
+        }
+
+        let mut diagnostics = Vec::new();
+        for (key, _) in json.entries() {
+            if !KEYS.contains(&key) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    key: key.to_string(),
+                    text: "unknown key, it is ignored".to_string(),
+                });
+            }
+        }
+
+        let mut config = Self::new(default_name);
+        if let Some(name) = string(&json, "name", &mut diagnostics) {
+            config.namespace = namespace_from(&name);
+            config.name = name;
+        }
+        if let Some(namespace) = string(&json, "namespace", &mut diagnostics) {
+            match is_valid_namespace(&namespace) {
+                | true => config.namespace = namespace,
+                | false => diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    key: "namespace".to_string(),
+                    text: "may only contain a-z, 0-9, '_', '-' and '.'".to_string(),
+                }),
+            }
+        }
+        if let Some(source) = string(&json, "source", &mut diagnostics) {
+            config.source_folder = source;
+        }
+        if let Some(output) = string(&json, "output", &mut diagnostics) {
+            config.output_folder = output;
+        }
+        if let Some(target) = string(&json, "target", &mut diagnostics) {
+            match Target::parse(&target) {
+                | Ok(target) => config.target = target,
+                | Err(error) => diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    key: "target".to_string(),
+                    text: error,
+                }),
+            }
+        }
+
+        for key in ["load", "tick"] {
+            if let Some(functions) = strings(&json, key, &mut diagnostics) {
+                match key {
+                    | "load" => config.load = functions,
+                    | _ => config.tick = functions,
+                }
+            }
+        }
+
+        if let Some(lints) = lints(&json, &mut diagnostics) {
+            config.lints = lints;
+        }
+        if let Some(limit) = count(&json, "unrollLimit", &mut diagnostics) {
+            config.unroll_limit = limit;
+        }
+
+        match diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            | true => Err(ConfigError(diagnostics)),
+            | false => Ok((config, diagnostics)),
+        }
+    }
+
+    /// Loads the config at `path`, printing any warnings to stderr. The pack is named
+    /// after the folder holding the config unless it says otherwise.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|error| {
+            format!(
+                "Couldn't read {}: {}",
+                path.display(),
+                error
+            )
+        })?;
+        let default_name = path
+            .canonicalize()?
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let (config, warnings) = Self::parse(&text, &default_name)?;
+        for warning in warnings {
+            eprintln!("{}", warning);
+        }
+        Ok(config)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = object! {
+            name: self.name.as_str(),
+            namespace: self.namespace.as_str(),
+            source: self.source_folder.as_str(),
+            output: self.output_folder.as_str(),
+            target: self.target.to_string()
+        };
+        if !self.load.is_empty() {
+            json["load"] = self.load.clone().into();
+        }
+        if !self.tick.is_empty() {
+            json["tick"] = self.tick.clone().into();
+        }
+        let mut lints = JsonValue::new_object();
+        for lint in Lint::ALL {
+            let level = self.lints.level(lint);
+            if level != Level::Warn {
+                lints[lint.name()] = level.name().into();
+            }
+        }
+        if !lints.is_empty() {
+            json["lints"] = lints;
+        }
+        if self.unroll_limit != Options::default().unroll_limit {
+            json["unrollLimit"] = self.unroll_limit.into();
+        }
+        json
+    }
+}
+
+/// Reads an optional string key, reporting values of any other type.
+fn string(json: &JsonValue, key: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<String> {
+    let value = &json[key];
+    if value.is_null() {
+        return None;
+    }
+    match value.as_str() {
+        | Some(value) => Some(value.to_string()),
+        | None => {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                key: key.to_string(),
+                text: "expected a string".to_string(),
+            });
+            None
+        }
+    }
+}
+
+/// Reads an optional non-negative integer, reporting values of any other type.
+fn count(json: &JsonValue, key: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<usize> {
+    let value = &json[key];
+    if value.is_null() {
+        return None;
+    }
+    let count = value.as_usize();
+    if count.is_none() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            key: key.to_string(),
+            text: "expected a non-negative integer".to_string(),
+        });
+    }
+    count
+}
+
+/// Reads an optional array of strings, reporting values of any other type.
+fn strings(json: &JsonValue, key: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<Vec<String>> {
+    let value = &json[key];
+    if value.is_null() {
+        return None;
+    }
+    let strings: Option<Vec<String>> = match value.is_array() {
+        | true => value
+            .members()
+            .map(|member| member.as_str().map(str::to_string))
+            .collect(),
+        | false => None,
+    };
+    if strings.is_none() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            key: key.to_string(),
+            text: "expected an array of function ids".to_string(),
+        });
+    }
+    strings
+}
+
+/// Reads the levels of lints, like `{ "unused-variable": "allow" }`. Unknown
+/// lints are ignored with a warning, as they may be from another version.
+fn lints(json: &JsonValue, diagnostics: &mut Vec<Diagnostic>) -> Option<Lints> {
+    let value = &json["lints"];
+    if value.is_null() {
+        return None;
+    }
+    if !value.is_object() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            key: "lints".to_string(),
+            text: "expected an object of lint levels".to_string(),
+        });
+        return None;
+    }
+
+    let mut lints = Lints::default();
+    for (name, level) in value.entries() {
+        let key = format!(
+            "lints.{}",
+            name
+        );
+        let lint = match Lint::parse(name) {
+            | Some(lint) => lint,
+            | None => {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    key,
+                    text: "unknown lint, it is ignored".to_string(),
+                });
+                continue;
+            }
+        };
+        match level.as_str().and_then(Level::parse) {
+            | Some(level) => lints.set(lint, level),
+            | None => diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                key,
+                text: "expected \"allow\", \"warn\" or \"deny\"".to_string(),
+            }),
+        }
+    }
+    Some(lints)
+}
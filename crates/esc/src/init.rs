@@ -1,55 +1,213 @@
 use std::{
     env::current_dir,
     error::Error,
-    fs::{create_dir, File},
-    io::Write,
-    path::Path,
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
 };
 
-use dialoguer::{theme::ColorfulTheme, Confirm, Input};
-use json::object;
+use compiler::target::Target;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
 
-use crate::Config;
+use crate::config::{is_valid_namespace, namespace_from, Config};
 
-pub fn init() -> Result<Option<Config>, Box<dyn Error>> {
+/// Sample project a new project starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    Empty,
+    HelloWorld,
+    TickLoop,
+    Library,
+}
+impl Template {
+    pub const ALL: [Template; 4] = [
+        Template::Empty,
+        Template::HelloWorld,
+        Template::TickLoop,
+        Template::Library,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            | Template::Empty => "empty",
+            | Template::HelloWorld => "hello-world",
+            | Template::TickLoop => "tick-loop",
+            | Template::Library => "library",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|template| template.name() == name)
+    }
+
+    /// Source files of the template, relative to the namespace folder.
+    fn sources(&self, namespace: &str) -> Vec<(&'static str, String)> {
+        match self {
+            | Template::Empty => vec![],
+            | Template::HelloWorld => vec![(
+                "main.es",
+                "raw \"say Hello, world!\"\n".to_string(),
+            )],
+            | Template::TickLoop => vec![
+                (
+                    "load.es",
+                    "raw \"scoreboard objectives add ticks dummy\"\n".to_string(),
+                ),
+                (
+                    "tick.es",
+                    "raw \"scoreboard players add #total ticks 1\"\n".to_string(),
+                ),
+            ],
+            | Template::Library => vec![(
+                "lib.es",
+                format!(
+                    "function greet() {{\n    raw \"say Hello from {}\"\n}}\n",
+                    namespace
+                ),
+            )],
+        }
+    }
+
+    /// Functions the template runs on load and every tick.
+    fn tags(&self) -> (Vec<String>, Vec<String>) {
+        match self {
+            | Template::HelloWorld => (vec!["main".to_string()], vec![]),
+            | Template::TickLoop => (vec!["load".to_string()], vec!["tick".to_string()]),
+            | Template::Empty | Template::Library => (vec![], vec![]),
+        }
+    }
+}
+
+/// Settings given on the command line, so they aren't asked for.
+#[derive(Default)]
+pub struct InitOptions<'a> {
+    pub name: Option<&'a str>,
+    pub namespace: Option<&'a str>,
+    pub source: Option<&'a str>,
+    pub output: Option<&'a str>,
+    pub target: Option<&'a str>,
+    pub template: Option<&'a str>,
+    /// Uses defaults for anything not given instead of asking
+    pub yes: bool,
+}
+
+/// Settles the settings of a new project, asking for those not given unless
+/// `options.yes` is set.
+pub fn init(options: &InitOptions) -> Result<Option<(Config, Template)>, Box<dyn Error>> {
     let theme = ColorfulTheme::default();
 
+    let cur_dir = current_dir()?
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let template = match options.template {
+        | Some(name) => Some(Template::parse(name).ok_or_else(|| {
+            let names: Vec<&str> = Template::ALL.iter().map(Template::name).collect();
+            format!(
+                "Unknown template \"{}\", expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        })?),
+        | None => None,
+    };
+    if let Some(namespace) = options.namespace {
+        if !is_valid_namespace(namespace) {
+            return Err(format!(
+                "Invalid namespace \"{}\", it may only contain a-z, 0-9, '_', '-' and '.'",
+                namespace
+            )
+            .into());
+        }
+    }
+    let target = options.target.map(Target::parse).transpose()?;
+
+    if options.yes {
+        let mut config = Config::new(options.name.unwrap_or(&cur_dir));
+        if let Some(namespace) = options.namespace {
+            config.namespace = namespace.to_string();
+        }
+        if let Some(source) = options.source {
+            config.source_folder = source.to_string();
+        }
+        if let Some(output) = options.output {
+            config.output_folder = output.to_string();
+        }
+        if let Some(target) = target {
+            config.target = target;
+        }
+        return Ok(Some((
+            config,
+            template.unwrap_or(Template::HelloWorld),
+        )));
+    }
+
     println!("\nThis utility will walk you through creating an EnderScript project.\nIt only covers the most common items, and tries to guess sensible defaults.\n\nPress ^C at any time to quit.");
 
-    let cur_dir = current_dir()
-        .unwrap()
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string();
-
-    let name = Input::with_theme(&theme)
-        .with_prompt("Name")
-        .default(cur_dir.clone())
-        .interact_text()?;
-
-    let namespace = Input::with_theme(&theme)
-        .with_prompt("Namespace")
-        .default(cur_dir)
-        .interact_text()?;
-
-    let source_folder = Input::with_theme(&theme)
-        .with_prompt("Source folder")
-        .default("./src".to_string())
-        .interact_text()?;
-
-    let output_folder = Input::with_theme(&theme)
-        .with_prompt("Output folder")
-        .default("./out".to_string())
-        .interact_text()?;
-
-    let gen_file_struct = Confirm::with_theme(&theme)
-        .with_prompt("Generate coresponding file structure?")
-        .default(true)
-        .interact()?;
+    let name = match options.name {
+        | Some(name) => name.to_string(),
+        | None => Input::with_theme(&theme)
+            .with_prompt("Name")
+            .default(cur_dir)
+            .interact_text()?,
+    };
+
+    let namespace = match options.namespace {
+        | Some(namespace) => namespace.to_string(),
+        | None => Input::with_theme(&theme)
+            .with_prompt("Namespace")
+            .default(namespace_from(&name))
+            .validate_with(|input: &String| match is_valid_namespace(input) {
+                | true => Ok(()),
+                | false => Err("A namespace may only contain a-z, 0-9, '_', '-' and '.'"),
+            })
+            .interact_text()?,
+    };
+
+    let source_folder = match options.source {
+        | Some(source) => source.to_string(),
+        | None => Input::with_theme(&theme)
+            .with_prompt("Source folder")
+            .default("./src".to_string())
+            .interact_text()?,
+    };
 
-    println!("");
+    let output_folder = match options.output {
+        | Some(output) => output.to_string(),
+        | None => Input::with_theme(&theme)
+            .with_prompt("Output folder")
+            .default("./out".to_string())
+            .interact_text()?,
+    };
+
+    let target = match target {
+        | Some(target) => target,
+        | None => {
+            let target: String = Input::with_theme(&theme)
+                .with_prompt("Target Minecraft version")
+                .default(Target::default().to_string())
+                .validate_with(|input: &String| Target::parse(input).map(|_| ()))
+                .interact_text()?;
+            Target::parse(&target)?
+        }
+    };
+
+    let template = match template {
+        | Some(template) => template,
+        | None => {
+            let names: Vec<&str> = Template::ALL.iter().map(Template::name).collect();
+            let index = Select::with_theme(&theme)
+                .with_prompt("Template")
+                .items(&names)
+                .default(1)
+                .interact()?;
+            Template::ALL[index]
+        }
+    };
+
+    println!();
 
     let proceed = Confirm::with_theme(&theme)
         .with_prompt("Proceed with the setup?")
@@ -57,37 +215,82 @@ pub fn init() -> Result<Option<Config>, Box<dyn Error>> {
         .interact()?;
 
     if proceed {
-        Ok(Some(Config {
-            name,
-            namespace,
-            gen_file_struct,
-            output_folder,
-            source_folder,
-        }))
+        let mut config = Config::new(name);
+        config.namespace = namespace;
+        config.source_folder = source_folder;
+        config.output_folder = output_folder;
+        config.target = target;
+        Ok(Some((config, template)))
     } else {
         Ok(None)
     }
 }
 
-pub fn gen_files(root: &Path, config: &Config) -> Result<(), Box<dyn Error>> {
-    let mut file = File::create(root.join("esconfig.json"))?;
-    file.write_all(
-        object! {
-            name: config.name.as_str(),
-            source: config.source_folder.as_str(),
-            output: config.output_folder.as_str()
-        }
-        .pretty(4)
-        .as_bytes(),
-    )?;
+/// Fails if `root` already holds a project, unless it may be overwritten.
+pub fn check_existing(root: &Path, force: bool) -> Result<(), Box<dyn Error>> {
+    if !force && root.join("esconfig.json").exists() {
+        return Err("esconfig.json already exists, pass --force to overwrite the project".into());
+    }
+    Ok(())
+}
+
+/// Writes the config, the template's sources and a .gitignore of a new project.
+/// Existing files are only overwritten if `force` is set, and an existing
+/// .gitignore is added to instead.
+pub fn gen_files(root: &Path, config: &mut Config, template: Template, force: bool) -> Result<(), Box<dyn Error>> {
+    let (load, tick) = template.tags();
+    config.load = load;
+    config.tick = tick;
+
+    let source_dir = root
+        .join(&config.source_folder)
+        .join(&config.namespace);
+
+    let mut files: Vec<(PathBuf, String)> = vec![(
+        root.join("esconfig.json"),
+        config.to_json().pretty(4) + "\n",
+    )];
+    for (file, contents) in template.sources(&config.namespace) {
+        files.push((source_dir.join(file), contents));
+    }
 
-    if config.gen_file_struct {
-        create_dir(root.join(&config.source_folder))?;
-        create_dir(root.join(&config.output_folder))?;
+    if !force {
+        if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+            return Err(format!(
+                "{} already exists, pass --force to overwrite it",
+                path.display()
+            )
+            .into());
+        }
+    }
 
-        let source_dir = root.join(&config.source_folder);
+    create_dir_all(&source_dir)?;
+    create_dir_all(root.join(&config.output_folder))?;
+    for (path, contents) in files {
+        fs::write(path, contents)?;
+    }
 
-        create_dir(source_dir.join(&config.namespace))?;
+    let gitignore = root.join(".gitignore");
+    let mut ignored = fs::read_to_string(&gitignore).unwrap_or_default();
+    let output = format!(
+        "/{}/",
+        config
+            .output_folder
+            .trim_start_matches("./")
+            .trim_end_matches('/')
+    );
+    let missing: Vec<String> = [output, "/.esc-cache/".to_string()]
+        .into_iter()
+        .filter(|entry| !ignored.lines().any(|line| line == entry))
+        .collect();
+    if !missing.is_empty() {
+        if !ignored.is_empty() && !ignored.ends_with('\n') {
+            ignored.push('\n');
+        }
+        for entry in missing {
+            ignored += &(entry + "\n");
+        }
+        fs::write(gitignore, ignored)?;
     }
 
     Ok(())
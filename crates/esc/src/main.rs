@@ -1,78 +1,40 @@
-use compiler::compiler;
-use parser::parser;
-use std::{fs::{self, File, read_dir, create_dir_all}, env::current_dir, error::Error, io::Read, ffi::OsString, path::Path};
-use clap::{Command, crate_version, crate_name, arg};
+use std::{env::current_dir, error::Error, process::exit};
+use clap::{ArgMatches, Command, crate_version, crate_name, arg};
 
+mod build;
+mod cache;
 mod init;
 mod config;
+mod report;
+mod watch;
 
-use config::Config;
-use init::{init, gen_files};
-
-fn build_entry(filename: String) -> Result<(), String> {
-    let text = fs::read_to_string(filename).expect("Something went wrong reading the file");
-
-    let ast = match parser::parse(text) {
-        | Ok(expr) => {
-            println!(
-                "{:#?}",
-                expr
-            );
-            expr
-        }
-        | Err(error) => {
-            return Err(error.to_string());
-        }
-    };
-    let out = match compiler::compile(ast) {
-        | Ok(out) => {
-            for fun in out {
-                println!(
-                    "{}\n",
-                    fun
-                )
-            }
-        }
-        | Err(error) => {
-            return Err(error.to_string());
-        }
-    };
-
-    Ok(())
-}
-
-fn build_namespace(namespace: OsString) {
+use build::{build, check};
+use config::SCHEMA;
+use report::{ColorChoice, MessageFormat};
+use utilities::message::MessageType;
+use watch::watch;
+use init::{check_existing, init, gen_files, InitOptions, Template};
 
+fn message_format(matches: &ArgMatches) -> MessageFormat {
+    let color = matches
+        .value_of("color")
+        .and_then(ColorChoice::parse)
+        .unwrap_or_default();
+    MessageFormat::parse(matches.value_of("message-format").unwrap_or("human"), color).unwrap()
 }
 
-fn build(config_path: Option<&str>, source_path: Option<&str>, output_path: Option<&str>) -> Result<(), Box<dyn Error>> {
-    let mut file = File::open(config_path.unwrap_or("./esconfig.json"))?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-
-    let config = json::parse(contents.as_str())?;
-
-    let source_path = Path::new(source_path.unwrap_or_else(|| config["source"].as_str().unwrap()));
-    let output_path = Path::new(output_path.unwrap_or_else(|| config["output"].as_str().unwrap()));
-    let name = config["name"].as_str().unwrap();
-
-    let pack = output_path.join(name).as_path();
-
-    create_dir_all(pack)?;
-
-    let mcmeta = File::create(pack.join("pack.mcmeta"));
-
-    for path in read_dir(source_path)? {
-        if let Ok(path) = path {
-            let meta = path.metadata()?;
-        
-            if meta.is_dir() {
-                build_namespace(path.file_name());
-            }
+/// Builds the project, exiting with an error code if it fails.
+fn run_build(matches: &ArgMatches) {
+    let use_cache = !matches.is_present("no-cache");
+    let format = message_format(matches);
+    match build(matches.value_of("config"), matches.value_of("source"), matches.value_of("output"), matches.value_of("target"), use_cache, format) {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(error) => {
+            format.status(error);
+            exit(1);
         }
     }
-
-    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -82,36 +44,462 @@ fn main() -> Result<(), Box<dyn Error>> {
         .subcommand(
             Command::new("init")
                 .alias("i")
+                .arg(arg!(--name [NAME] "Name of the project"))
+                .arg(arg!(--namespace [NAMESPACE] "Namespace of the project"))
+                .arg(
+                    arg!(--template [TEMPLATE] "Sample project to start from")
+                        .possible_values(Template::ALL.map(|template| template.name()))
+                )
+                .arg(arg!(-y --yes "Uses defaults for anything not given instead of asking"))
+                .arg(arg!(-f --force "Overwrites an existing project"))
         )
         .subcommand(
             Command::new("build")
                 .alias("b")
+                .arg(arg!(-w --watch "Rebuilds changed files whenever the sources change"))
+                .arg(arg!(--"no-cache" "Compiles every file instead of reusing unchanged ones from .esc-cache"))
+        )
+        .subcommand(
+            Command::new("check")
+                .alias("c")
+                .about("Reports the diagnostics of the project without writing anything")
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Describes a diagnostic code in detail, with examples")
+                .arg(arg!(<CODE> "Code of the diagnostic, like ES102E"))
+        )
+        .subcommand(
+            Command::new("schema")
+                .about("Prints the JSON schema of esconfig.json")
         )
         .arg(
             arg!(-c --config [CONFIG] "Optionally sets a config file to use")
+                .global(true)
         )
         .arg(
             arg!(-s --source [SOURCE] "Overrides the source folder specified in esconfig.json")
+                .global(true)
         )
         .arg(
             arg!(-o --output [OUTPUT] "Overrides the output folder specified in esconfig.json")
+                .global(true)
+        )
+        .arg(
+            arg!(-t --target [TARGET] "Overrides the Minecraft version specified in esconfig.json")
+                .global(true)
+        )
+        .arg(
+            arg!(--"message-format" [FORMAT] "Prints diagnostics for people, as JSON lines for tools, or as HTML")
+                .possible_values(MessageFormat::NAMES)
+                .global(true)
+        )
+        .arg(
+            arg!(--color [WHEN] "Colors diagnostics for people, by default when printing to a terminal and NO_COLOR isn't set")
+                .possible_values(ColorChoice::NAMES)
+                .global(true)
         )
         .get_matches();
     
     match matches.subcommand() {
-        Some(("init", _)) => {
-            match init() {
-                Ok(Some(config)) => gen_files(current_dir().unwrap().as_path(), &config)?,
-                Ok(None) => println!("Setup wizard aborted."),
-                Err(error) => println!("err: {}", error)
+        Some(("init", matches)) => {
+            let root = current_dir()?;
+            let force = matches.is_present("force");
+            let options = InitOptions {
+                name: matches.value_of("name"),
+                namespace: matches.value_of("namespace"),
+                source: matches.value_of("source"),
+                output: matches.value_of("output"),
+                target: matches.value_of("target"),
+                template: matches.value_of("template"),
+                yes: matches.is_present("yes"),
+            };
+
+            let result = check_existing(&root, force)
+                .and_then(|_| init(&options))
+                .and_then(|project| match project {
+                    Some((mut config, template)) => gen_files(&root, &mut config, template, force),
+                    None => {
+                        println!("Setup wizard aborted.");
+                        Ok(())
+                    }
+                });
+            if let Err(error) = result {
+                println!("err: {}", error);
+                exit(1);
+            }
+        },
+        Some(("build", matches)) if matches.is_present("watch") => {
+            watch(matches.value_of("config"), matches.value_of("source"), matches.value_of("output"), matches.value_of("target"), !matches.is_present("no-cache"), message_format(matches))?;
+        },
+        Some(("build", matches)) => {
+            run_build(matches);
+        },
+        Some(("check", matches)) => {
+            let format = message_format(matches);
+            match check(matches.value_of("config"), matches.value_of("source"), matches.value_of("target"), format) {
+                Ok(true) => {}
+                Ok(false) => exit(1),
+                Err(error) => {
+                    format.status(error);
+                    exit(1);
+                }
+            }
+        },
+        Some(("explain", matches)) => {
+            let code = matches.value_of("CODE").unwrap().to_uppercase();
+            match MessageType::from_code(&code) {
+                Some(message_type) => print!("{}", message_type.explanation()),
+                None => {
+                    let codes: Vec<&str> = MessageType::ALL.iter().map(|message_type| message_type.code()).collect();
+                    println!("err: Unknown code '{}', expected one of {}", code, codes.join(", "));
+                    exit(1);
+                }
             }
         },
+        Some(("schema", _)) => print!("{}", SCHEMA),
         Some((_, _)) => unreachable!(),
 
         None => {
-            build(matches.value_of("config"), matches.value_of("source"), matches.value_of("output"))?;
+            run_build(&matches);
         },
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs, path::PathBuf};
+
+    use compiler::{
+        compiler::Options,
+        datapack::ResourceLocation,
+        lint::{Level, Lint},
+        module::ModuleGraph,
+    };
+    use parser::program::Program;
+    use utilities::message::{Message, MessageType, Plain};
+
+    use super::build::{build, check, compile_module, load_program, module_graph, Entry, EntryError};
+    use super::cache::Cache;
+    use super::config::{Config, Severity};
+    use super::init::{gen_files, Template};
+    use super::report::{ColorChoice, MessageFormat};
+    use super::watch::Watcher;
+
+    #[test]
+    fn config_defaults_missing_keys() {
+        let (config, warnings) = Config::parse("{ \"name\": \"My Pack\" }", "folder").unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(config.name, "My Pack");
+        assert_eq!(config.namespace, "my_pack");
+        assert_eq!(config.source_folder, "./src");
+        assert_eq!(config.output_folder, "./out");
+        assert_eq!(config.target.to_string(), "1.19");
+
+        let (config, _) = Config::parse("{}", "folder").unwrap();
+        assert_eq!(config.name, "folder");
+    }
+
+    #[test]
+    fn config_reports_bad_keys() {
+        let (_, warnings) = Config::parse("{ \"ouput\": \"./out\" }", "folder").unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(warnings[0].key, "ouput");
+
+        let errors = Config::parse("{ \"source\": 1, \"namespace\": \"Bad\", \"target\": \"1.2\" }", "folder")
+            .err()
+            .unwrap()
+            .0;
+        let keys: Vec<&str> = errors.iter().map(|error| error.key.as_str()).collect();
+        assert_eq!(keys, vec!["namespace", "source", "target"]);
+    }
+
+    #[test]
+    fn config_sets_lint_levels() {
+        let (config, warnings) = Config::parse(
+            "{ \"lints\": { \"unused-variable\": \"allow\", \"shadowing\": \"deny\", \"pedantic\": \"warn\" } }",
+            "folder",
+        )
+        .unwrap();
+        assert_eq!(config.lints.level(Lint::UnusedVariable), Level::Allow);
+        assert_eq!(config.lints.level(Lint::Shadowing), Level::Deny);
+        assert_eq!(config.lints.level(Lint::UnreachableCode), Level::Warn);
+        assert_eq!(warnings[0].key, "lints.pedantic");
+
+        let errors = Config::parse("{ \"lints\": { \"shadowing\": \"forbid\" } }", "folder")
+            .err()
+            .unwrap()
+            .0;
+        assert_eq!(errors[0].key, "lints.shadowing");
+    }
+
+    #[test]
+    fn config_sets_the_unroll_limit() {
+        let (config, _) = Config::parse("{ \"unrollLimit\": 4 }", "folder").unwrap();
+        assert_eq!(config.unroll_limit, 4);
+        assert_eq!(config.to_json()["unrollLimit"], 4);
+
+        let errors = Config::parse("{ \"unrollLimit\": -1 }", "folder")
+            .err()
+            .unwrap()
+            .0;
+        assert_eq!(errors[0].key, "unrollLimit");
+    }
+
+    #[test]
+    fn entries_need_valid_names() {
+        let entry = |namespace: &str, path: &str| Entry {
+            namespace: namespace.to_string(),
+            root: PathBuf::from("src").join(namespace),
+            path: PathBuf::from(path),
+        };
+
+        assert!(entry("demo", "util/main.es").check_names().is_ok());
+        let error = entry("demo", "Main.es").check_names().err().unwrap();
+        assert!(error.ends_with("rename it to 'main'"), "{}", error);
+        assert!(entry("MyNs", "main.es").check_names().is_err());
+    }
+
+    #[test]
+    fn color_choice_picks_the_human_format() {
+        assert_eq!(MessageFormat::parse("human", ColorChoice::Always), Some(MessageFormat::Ansi));
+        assert_eq!(MessageFormat::parse("human", ColorChoice::Never), Some(MessageFormat::Plain));
+        assert_eq!(MessageFormat::parse("json", ColorChoice::Always), Some(MessageFormat::Json));
+        assert_eq!(MessageFormat::parse("xml", ColorChoice::Auto), None);
+        assert_eq!(ColorChoice::parse("never"), Some(ColorChoice::Never));
+    }
+
+    /// A project folder for one test, deleted when it goes out of scope.
+    struct TempProject {
+        root: PathBuf,
+    }
+    impl TempProject {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = temp_dir().join(format!("esc-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            let project = Self { root };
+            for (path, contents) in files {
+                project.write(path, contents);
+            }
+            project
+        }
+
+        fn path(&self, path: &str) -> PathBuf {
+            self.root.join(path)
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let file = self.path(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, contents).unwrap();
+        }
+    }
+    impl Drop for TempProject {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn init_scaffolds_templates_without_overwriting() {
+        let project = TempProject::new("init", &[(".gitignore", "target/")]);
+        let root = &project.root;
+
+        let mut config = Config::new("Demo");
+        gen_files(root, &mut config, Template::parse("tick-loop").unwrap(), false).unwrap();
+
+        assert!(project.path("src/demo/load.es").exists());
+        assert!(project.path("src/demo/tick.es").exists());
+        assert_eq!(fs::read_to_string(project.path(".gitignore")).unwrap(), "target/\n/out/\n/.esc-cache/\n");
+        let (written, _) = Config::parse(&fs::read_to_string(project.path("esconfig.json")).unwrap(), "").unwrap();
+        assert_eq!(written.tick, vec!["tick"]);
+
+        assert!(gen_files(root, &mut config, Template::Empty, false).is_err());
+        assert!(gen_files(root, &mut config, Template::Empty, true).is_ok());
+    }
+
+    #[test]
+    fn watcher_rebuilds_changed_files() {
+        let project = TempProject::new(
+            "watch",
+            &[
+                ("esconfig.json", "{ \"name\": \"demo\" }"),
+                ("src/demo/a.es", "raw \"say a\""),
+                ("src/demo/b.es", "raw \"say b\""),
+            ],
+        );
+
+        let source = project.path("src");
+        let output = project.path("out");
+        let mut watcher = Watcher::new(
+            &project.path("esconfig.json"),
+            source.to_str(),
+            output.to_str(),
+            None,
+            false,
+            MessageFormat::Plain,
+        );
+        let function = output.join("demo/data/demo/functions/b.mcfunction");
+
+        assert_eq!(watcher.rebuild().unwrap(), (2, true));
+        assert_eq!(watcher.rebuild().unwrap(), (0, true));
+
+        project.write("src/demo/b.es", "raw \"say bb\"");
+        assert_eq!(watcher.rebuild().unwrap(), (1, true));
+        assert!(fs::read_to_string(&function).unwrap().contains("say bb"));
+
+        // the last good output is kept while a file has errors
+        project.write("src/demo/b.es", "let");
+        fs::remove_file(project.path("src/demo/a.es")).unwrap();
+        assert_eq!(watcher.rebuild().unwrap(), (2, false));
+        assert!(fs::read_to_string(&function).unwrap().contains("say bb"));
+        assert!(!output.join("demo/data/demo/functions/a.mcfunction").exists());
+    }
+
+    #[test]
+    fn imports_resolve_across_files() {
+        let project = TempProject::new(
+            "modules",
+            &[
+                ("esconfig.json", "{ \"name\": \"demo\" }"),
+                ("src/demo/lib/util.es", "export function greet() {\n    raw \"say Hi!\"\n}"),
+                ("src/demo/main.es", "import { greet } from \"./lib/util\"\n\ngreet()"),
+            ],
+        );
+
+        let config = project.path("esconfig.json");
+        let source = project.path("src");
+        let output = project.path("out");
+        assert!(build(config.to_str(), source.to_str(), output.to_str(), None, false, MessageFormat::Plain).unwrap());
+        let main = fs::read_to_string(output.join("demo/data/demo/functions/main.mcfunction")).unwrap();
+        assert!(main.contains("function demo:lib/util/greet"));
+
+        // files importing a changed one are checked again
+        let mut watcher = Watcher::new(&config, source.to_str(), output.to_str(), None, false, MessageFormat::Plain);
+        assert_eq!(watcher.rebuild().unwrap(), (2, true));
+        project.write("src/demo/lib/util.es", "function greet() {\n    raw \"say Hi!\"\n}");
+        assert_eq!(watcher.rebuild().unwrap(), (2, false));
+    }
+
+    #[test]
+    fn cached_units_are_reused() {
+        let text = "function tick() {\n  raw \"say tick\"\n  tick()\n}\nlet a: int\nlet b = a + a\na = b";
+        let project = TempProject::new("cache", &[("main.es", text)]);
+
+        let entry = Entry {
+            namespace: "demo".to_string(),
+            root: project.root.clone(),
+            path: "main.es".into(),
+        };
+        let options = Options::default();
+        let mut cache = Cache::new(&project.path(".esc-cache"));
+        let (program, loaded, _) = load_program(std::slice::from_ref(&entry), MessageFormat::Plain);
+        let modules = module_graph(&program, &loaded);
+        let compiled = compile_module(&program, &modules, loaded[0].1, &entry.location(), &options, Some(&mut cache))
+            .map_err(|error| error.to_string())
+            .unwrap();
+
+        let key = Cache::key(&entry.location(), &options, text);
+        let file = compiled.warnings[0].cursor.file;
+        let cached = cache.get(&key, file).unwrap();
+        assert_eq!(cached.functions.len(), compiled.functions.len());
+        assert_eq!(cached.functions[1].body, compiled.functions[1].body);
+        let render = |warnings: &[Message]| -> Vec<String> {
+            warnings
+                .iter()
+                .map(|warning| warning.render(&Plain, &program.sources))
+                .collect()
+        };
+        assert_eq!(render(&cached.warnings), render(&compiled.warnings));
+        assert!(cached.warnings.iter().any(|warning| !warning.labels.is_empty()));
+
+        assert!(cache.get(&Cache::key(&entry.location(), &options, "raw \"say\""), file).is_none());
+    }
+
+    #[test]
+    fn check_writes_nothing() {
+        let project = TempProject::new(
+            "check",
+            &[
+                ("esconfig.json", "{ \"output\": \"./out\" }"),
+                ("src/demo/main.es", "raw \"say hi\""),
+            ],
+        );
+
+        let config = project.path("esconfig.json");
+        let source = project.path("src");
+        assert!(check(config.to_str(), source.to_str(), None, MessageFormat::Plain).unwrap());
+
+        project.write("src/demo/bad.es", "let");
+        assert!(!check(config.to_str(), source.to_str(), None, MessageFormat::Plain).unwrap());
+        assert!(!project.path("out").exists());
+        assert!(!project.path(".esc-cache").exists());
+    }
+
+    /// The code of the first `es` block following a heading.
+    fn example<'a>(explanation: &'a str, heading: &str) -> &'a str {
+        let section = &explanation[explanation.find(heading).unwrap()..];
+        let code = &section[section.find("```es\n").unwrap() + 6..];
+        &code[..code.find("```").unwrap()]
+    }
+
+    /// Codes of every diagnostic of a file, next to a `util.es` exporting
+    /// `greet` but not `shout`.
+    fn codes(text: &str) -> Vec<&'static str> {
+        let location = ResourceLocation::new("demo", "main");
+        let util = "export function greet() {\n    raw \"say Hi!\"\n}\n\nfunction shout() {\n    raw \"say HI!\"\n}";
+        let mut program = Program::new();
+        let file = program.add("main.es", text);
+        let util_file = program.add("util.es", util);
+        let modules = ModuleGraph::new([
+            (location.clone(), program.module(file).unwrap().ast.as_slice()),
+            (ResourceLocation::new("demo", "util"), program.module(util_file).unwrap().ast.as_slice()),
+        ]);
+        match compile_module(&program, &modules, file, &location, &Options::default(), None) {
+            | Ok(unit) => unit
+                .warnings
+                .iter()
+                .map(|message| message.message_type.code())
+                .collect(),
+            | Err(EntryError::Diagnostics(messages)) => messages
+                .iter()
+                .map(|message| message.message_type.code())
+                .collect(),
+            | Err(error) => panic!("{}", error),
+        }
+    }
+
+    #[test]
+    fn explanation_examples_match_their_code() {
+        // reserved codes the compiler doesn't emit yet
+        let reserved = [
+            MessageType::MissingMemberDeclaration,
+            MessageType::MissingCaseSeparatorOrClosure,
+            MessageType::UnknownType,
+        ];
+
+        for message_type in MessageType::ALL {
+            if reserved.contains(&message_type) {
+                continue;
+            }
+            let explanation = message_type.explanation();
+            let code = message_type.code();
+
+            let erroneous = codes(example(explanation, "## Erroneous example"));
+            assert!(erroneous.contains(&code), "{}: erroneous example reports {:?}", code, erroneous);
+            let corrected = codes(example(explanation, "## Corrected example"));
+            // other lints may still apply to a short example
+            assert!(
+                !corrected.iter().any(|other| *other == code || MessageType::from_code(other).unwrap().is_error()),
+                "{}: corrected example reports {:?}",
+                code,
+                corrected
+            );
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    env,
+    fmt::Display,
+    io::{stdout, IsTerminal},
+};
+
+use utilities::{
+    message::{Ansi, Html, Message, Plain},
+    source::{Columns, SourceMap},
+};
+
+/// Whether human-readable diagnostics are colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Colored when stdout is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+impl ColorChoice {
+    pub const NAMES: [&'static str; 3] = ["auto", "always", "never"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            | "auto" => Some(ColorChoice::Auto),
+            | "always" => Some(ColorChoice::Always),
+            | "never" => Some(ColorChoice::Never),
+            | _ => None,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        match self {
+            | ColorChoice::Auto => {
+                stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+            | ColorChoice::Always => true,
+            | ColorChoice::Never => false,
+        }
+    }
+}
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// For people, with colors and box-drawing characters
+    Ansi,
+    /// For people, in ASCII without colors
+    Plain,
+    /// One JSON object per diagnostic and line on stdout, with everything else
+    /// going to stderr
+    Json,
+    /// One `<pre>` element per diagnostic on stdout, with everything else going
+    /// to stderr
+    Html,
+}
+impl MessageFormat {
+    pub const NAMES: [&'static str; 3] = ["human", "json", "html"];
+
+    /// The format of a `--message-format` name, where human diagnostics are
+    /// colored depending on `--color`.
+    pub fn parse(name: &str, color: ColorChoice) -> Option<Self> {
+        match name {
+            | "human" if color.enabled() => Some(MessageFormat::Ansi),
+            | "human" => Some(MessageFormat::Plain),
+            | "json" => Some(MessageFormat::Json),
+            | "html" => Some(MessageFormat::Html),
+            | _ => None,
+        }
+    }
+
+    pub fn message(&self, message: &Message, sources: &SourceMap) {
+        match self {
+            | MessageFormat::Ansi => println!(
+                "{}\n",
+                message.render(&Ansi, sources)
+            ),
+            | MessageFormat::Plain => println!(
+                "{}\n",
+                message.render(&Plain, sources)
+            ),
+            | MessageFormat::Json => println!("{}", message.to_json(sources, Columns::Scalars).dump()),
+            | MessageFormat::Html => println!("{}", message.render(&Html, sources)),
+        }
+    }
+
+    pub fn messages<'a>(&self, messages: impl IntoIterator<Item = &'a Message>, sources: &SourceMap) {
+        for message in messages {
+            self.message(message, sources);
+        }
+    }
+
+    /// Prints anything that isn't a diagnostic, like progress or a summary.
+    pub fn status(&self, text: impl Display) {
+        match self {
+            | MessageFormat::Ansi | MessageFormat::Plain => println!("{}", text),
+            | MessageFormat::Json | MessageFormat::Html => eprintln!("{}", text),
+        }
+    }
+}
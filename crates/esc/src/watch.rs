@@ -0,0 +1,283 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
+
+use compiler::datapack::{CompiledUnit, ResourceLocation};
+
+use crate::{
+    build::{assemble, compile_module, find_entries, load_program, module_graph, Entry, Settings},
+    cache::Cache,
+    report::MessageFormat,
+};
+
+/// How often the source folder is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// When a file was last modified along with its size, as mtimes may be too
+/// coarse to tell quick edits apart.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// A source file as of its last compilation.
+struct Compiled {
+    stamp: Stamp,
+    /// The last successful result, kept while the file has errors
+    unit: Option<CompiledUnit>,
+}
+
+/// State kept between rebuilds of a watched project.
+pub struct Watcher {
+    config_path: PathBuf,
+    source_path: Option<String>,
+    output_path: Option<String>,
+    target: Option<String>,
+    use_cache: bool,
+    format: MessageFormat,
+    settings: Option<Settings>,
+    cache: Option<Cache>,
+    /// When the config was last loaded, `None` before the first time
+    config_stamp: Option<Stamp>,
+    entries: BTreeMap<Entry, Compiled>,
+    /// Folder the pack was last written to
+    output_root: Option<PathBuf>,
+    /// Contents of every output file as last written
+    written: HashMap<PathBuf, String>,
+}
+impl Watcher {
+    pub fn new(
+        config_path: &Path,
+        source_path: Option<&str>,
+        output_path: Option<&str>,
+        target: Option<&str>,
+        use_cache: bool,
+        format: MessageFormat,
+    ) -> Self {
+        Self {
+            config_path: config_path.to_path_buf(),
+            source_path: source_path.map(str::to_string),
+            output_path: output_path.map(str::to_string),
+            target: target.map(str::to_string),
+            use_cache,
+            format,
+            settings: None,
+            cache: None,
+            config_stamp: None,
+            entries: BTreeMap::new(),
+            output_root: None,
+            written: HashMap::new(),
+        }
+    }
+
+    /// Reloads the config if it changed, starting over with every file.
+    /// Returns whether it did.
+    fn reload_config(&mut self) -> bool {
+        let config_stamp = Some(stamp(&self.config_path));
+        if config_stamp == self.config_stamp {
+            return false;
+        }
+        self.config_stamp = config_stamp;
+        self.entries.clear();
+
+        self.settings = match Settings::load(
+            &self.config_path,
+            self.source_path.as_deref(),
+            self.output_path.as_deref(),
+            self.target.as_deref(),
+        ) {
+            | Ok(settings) => {
+                self.cache = self
+                    .use_cache
+                    .then(|| Cache::new(&settings.cache_path));
+                Some(settings)
+            }
+            | Err(error) => {
+                self.format.status(format!("{}\n", error));
+                None
+            }
+        };
+        true
+    }
+
+    /// Recompiles the files changed since the last rebuild and rewrites the output
+    /// files they affect. Returns the number of files compiled or removed, along
+    /// with whether they all succeeded.
+    pub fn rebuild(&mut self) -> Result<(usize, bool), Box<dyn Error>> {
+        let config_changed = self.reload_config();
+        let settings = match &self.settings {
+            | Some(settings) => settings,
+            | None => return Ok((0, false)),
+        };
+
+        let entries = find_entries(settings)?;
+        let removed: Vec<ResourceLocation> = self
+            .entries
+            .keys()
+            .filter(|entry| !entries.contains(entry))
+            .map(Entry::location)
+            .collect();
+        self.entries.retain(|entry, _| entries.contains(entry));
+
+        let stamps: BTreeMap<&Entry, Stamp> = entries
+            .iter()
+            .map(|entry| (entry, stamp(&entry.file())))
+            .collect();
+        let changed: Vec<ResourceLocation> = entries
+            .iter()
+            .filter(|entry| {
+                self.entries
+                    .get(entry)
+                    .is_none_or(|previous| previous.stamp != stamps[entry])
+            })
+            .map(Entry::location)
+            .collect();
+        if changed.is_empty() && removed.is_empty() && !config_changed {
+            return Ok((0, true));
+        }
+
+        let options = settings.options();
+        // imports are resolved across the whole program, so every file is read
+        // again. Diagnostics are printed right away, so the program isn't kept
+        // between rebuilds.
+        let (program, loaded, errors) = load_program(&entries, self.format);
+        let modules = module_graph(&program, &loaded);
+        // the imports of modules importing a changed or removed one may no
+        // longer resolve
+        let mut stale = changed.clone();
+        for location in changed.iter().chain(&removed) {
+            for dependent in modules.dependents(location) {
+                if !stale.contains(dependent) {
+                    stale.push(dependent.clone());
+                }
+            }
+        }
+
+        let mut compiled = 0;
+        let mut success = errors == 0;
+        for (entry, file) in loaded {
+            let location = entry.location();
+            if !stale.contains(&location) {
+                continue;
+            }
+
+            compiled += 1;
+            let previous = self.entries.get(&entry);
+            let unit = match compile_module(&program, &modules, file, &location, &options, self.cache.as_mut()) {
+                | Ok(unit) => {
+                    self.format.messages(&unit.warnings, &program.sources);
+                    Some(unit)
+                }
+                | Err(error) => {
+                    error.report(self.format, &program.sources);
+                    success = false;
+                    previous.and_then(|previous| previous.unit.clone())
+                }
+            };
+            self.entries.insert(
+                entry.clone(),
+                Compiled {
+                    stamp: stamps[&entry],
+                    unit,
+                },
+            );
+        }
+
+        if compiled > 0 || !removed.is_empty() || config_changed {
+            let datapack = assemble(
+                settings,
+                self.entries
+                    .values()
+                    .filter_map(|compiled| compiled.unit.as_ref()),
+            );
+            let root = settings.output_path.clone();
+            self.sync(&root, datapack.files())?;
+        }
+
+        Ok((compiled + removed.len(), success))
+    }
+
+    /// Writes the output files whose contents changed and deletes those that are
+    /// no longer generated.
+    fn sync(&mut self, root: &Path, files: Vec<(PathBuf, String)>) -> Result<(), Box<dyn Error>> {
+        if self.output_root.as_deref() != Some(root) {
+            self.output_root = Some(root.to_path_buf());
+            self.written.clear();
+        }
+
+        let mut written = HashMap::new();
+        for (path, contents) in files {
+            let unchanged = match self.written.get(&path) {
+                | Some(previous) => previous == &contents,
+                | None => fs::read_to_string(root.join(&path)).is_ok_and(|previous| previous == contents),
+            };
+            if !unchanged {
+                let file = root.join(&path);
+                fs::create_dir_all(file.parent().unwrap())?;
+                fs::write(file, &contents)?;
+            }
+            written.insert(path, contents);
+        }
+
+        for path in self.written.keys() {
+            if !written.contains_key(path) {
+                let _ = fs::remove_file(root.join(path));
+            }
+        }
+        self.written = written;
+        Ok(())
+    }
+}
+
+/// Rebuilds the project whenever its sources or config change, until interrupted.
+pub fn watch(
+    config_path: Option<&str>,
+    source_path: Option<&str>,
+    output_path: Option<&str>,
+    target: Option<&str>,
+    use_cache: bool,
+    format: MessageFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut watcher = Watcher::new(
+        Path::new(config_path.unwrap_or("./esconfig.json")),
+        source_path,
+        output_path,
+        target,
+        use_cache,
+        format,
+    );
+
+    format.status("Watching for changes, press ^C to stop.\n");
+    let mut last_error = None;
+    loop {
+        let start = Instant::now();
+        match watcher.rebuild() {
+            | Ok((0, _)) => {}
+            | Ok((files, true)) => format.status(format!(
+                "Rebuilt {} file(s) in {}ms\n",
+                files,
+                start.elapsed().as_millis()
+            )),
+            | Ok((_, false)) => format.status("Build failed, waiting for changes\n"),
+            // reported once, as it's likely to persist for a while
+            | Err(error) => {
+                let error = error.to_string();
+                if last_error.as_ref() != Some(&error) {
+                    format.status(format!("{}\n", error));
+                    last_error = Some(error);
+                }
+                sleep(POLL_INTERVAL);
+                continue;
+            }
+        }
+        last_error = None;
+        sleep(POLL_INTERVAL);
+    }
+}
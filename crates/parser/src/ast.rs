@@ -19,6 +19,12 @@ pub struct Argument {
     pub expression: Expression,
 }
 
+#[derive(Debug, Clone)]
+pub struct ImportedName {
+    pub name: Identifier,
+    pub cursor: Cursor,
+}
+
 #[derive(Debug, Clone)]
 pub enum Expression {
     FunctionDeclaration {
@@ -48,6 +54,24 @@ pub enum Expression {
         string: String,
         cursor: Cursor,
     },
+    ForLoop {
+        variable: Identifier,
+        start: Box<Expression>,
+        end: Box<Expression>,
+        body: Codeblock,
+        cursor: Cursor,
+    },
+    Import {
+        names: Vec<ImportedName>,
+        /// Path of the module, relative to the importing file
+        path: String,
+        cursor: Cursor,
+    },
+    /// A function other modules can import
+    Export {
+        declaration: Box<Expression>,
+        cursor: Cursor,
+    },
     Addition {
         left: Box<Expression>,
         right: Box<Expression>,
@@ -80,6 +104,8 @@ pub enum Expression {
         Identifier,
         Cursor,
     ),
+    /// A statement that failed to parse, already reported
+    ErrorNode(Cursor),
 }
 
 impl Expression {
@@ -90,6 +116,9 @@ impl Expression {
             | Expression::VariableDeclaration { cursor, .. } => cursor,
             | Expression::VariableAssign { cursor, .. } => cursor,
             | Expression::RawCode { cursor, .. } => cursor,
+            | Expression::ForLoop { cursor, .. } => cursor,
+            | Expression::Import { cursor, .. } => cursor,
+            | Expression::Export { cursor, .. } => cursor,
             | Expression::Addition { cursor, .. } => cursor,
             | Expression::Subtraction { cursor, .. } => cursor,
             | Expression::Multiplication { cursor, .. } => cursor,
@@ -97,6 +126,7 @@ impl Expression {
             | Expression::String(_, cursor) => cursor,
             | Expression::Integer(_, cursor) => cursor,
             | Expression::VariableAccess(_, cursor) => cursor,
+            | Expression::ErrorNode(cursor) => cursor,
         }
     }
 }
@@ -1,7 +1,10 @@
 pub mod ast;
 pub mod parser;
+pub mod program;
 mod token;
 
+pub use token::KEYWORDS;
+
 #[cfg(test)]
 mod tests {
     use super::token;
@@ -92,6 +95,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ranges() {
+        let mut lexer = Token::lexer("for i in 0..10");
+
+        assert_eq!(
+            lexer.next(),
+            Some(Token::For)
+        );
+        assert_eq!(
+            lexer.next(),
+            Some(Token::Identifier)
+        );
+        assert_eq!(
+            lexer.next(),
+            Some(Token::In)
+        );
+        assert_eq!(
+            lexer.next(),
+            Some(Token::Integer)
+        );
+        assert_eq!(
+            lexer.next(),
+            Some(Token::Range)
+        );
+        assert_eq!(
+            lexer.next(),
+            Some(Token::Integer)
+        );
+    }
+
     /*#[test]
     fn keywords() {
         let mut lexer = Token::lexer("function while if else or not and");
@@ -129,4 +162,85 @@ mod tests {
         assert_eq!(lexer.next(), Some(Token::Colon));
         assert_eq!(lexer.next(), Some(Token::Comma));
     }*/
+
+    #[test]
+    fn parse_errors_are_recovered_from() {
+        use super::ast::Expression;
+        use super::parser::parse;
+        use utilities::source::FileId;
+
+        let (ast, messages) = parse(FileId::default(), "let a = \nlet b = 1\nfunction f() {\n    let = 2\n    raw \"say hi\"\n}\nlet c = b");
+
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(ast[0], Expression::ErrorNode(_)));
+        assert!(matches!(ast[1], Expression::VariableDeclaration { .. }));
+        match &ast[2] {
+            | Expression::FunctionDeclaration { body, .. } => {
+                assert!(matches!(body[0], Expression::ErrorNode(_)));
+                assert!(matches!(body[1], Expression::RawCode { .. }));
+            }
+            | _ => panic!("expected a function"),
+        }
+        assert!(matches!(ast[3], Expression::VariableDeclaration { .. }));
+    }
+
+    #[test]
+    fn misspelled_keywords_are_suggested() {
+        use super::parser::parse;
+        use utilities::source::FileId;
+
+        let (_, messages) = parse(FileId::default(), "fucntion greet() {\n    raw \"say hi\"\n}\nlett a = 1\ncount 2");
+
+        let help: Vec<&[String]> = messages.iter().map(|message| message.help.as_slice()).collect();
+        assert_eq!(help[0], ["'fucntion' isn't a keyword, did you mean 'function'?"]);
+        assert_eq!(help[1], ["'lett' isn't a keyword, did you mean 'let'?"]);
+        assert!(help[2].is_empty());
+    }
+
+    #[test]
+    fn imports_and_exports_are_top_level() {
+        use super::ast::Expression;
+        use super::parser::parse;
+        use utilities::source::FileId;
+
+        let (ast, messages) = parse(FileId::default(), "import { greet, shout } from \"./util\"\nexport function hello() {\n    greet()\n}");
+        assert!(messages.is_empty());
+        match &ast[0] {
+            | Expression::Import { names, path, .. } => {
+                let names: Vec<&str> = names.iter().map(|name| name.name.as_str()).collect();
+                assert_eq!((names, path.as_str()), (vec!["greet", "shout"], "./util"));
+            }
+            | _ => panic!("expected an import"),
+        }
+        assert!(matches!(&ast[1], Expression::Export { declaration, .. } if matches!(**declaration, Expression::FunctionDeclaration { .. })));
+
+        let (_, messages) = parse(FileId::default(), "export let a = 1\nfunction f() {\n    import { greet } from \"./util\"\n    raw \"say hi\"\n}");
+        let codes: Vec<&str> = messages.iter().map(|message| message.message_type.code()).collect();
+        assert_eq!(codes, ["ES012E", "ES014E"]);
+
+        // `from` is only a keyword after the imported names
+        let (_, messages) = parse(FileId::default(), "let from = 1\nimport { from } from \"./util\"");
+        assert!(messages.is_empty());
+    }
+
+    #[test]
+    fn programs_keep_the_file_of_every_error() {
+        use super::program::Program;
+        use utilities::source::Columns;
+
+        let program = Program::parse([
+            ("src/demo/main.es", "let a = 1\nraw \"say hi\""),
+            ("src/demo/util.es", "let b = 2\nlet = 3"),
+        ]);
+
+        assert_eq!(program.modules.len(), 2);
+        let util = program.modules[1].file;
+        assert!(program.module(program.modules[0].file).unwrap().messages.is_empty());
+
+        let errors: Vec<_> = program.messages().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.sources.name(errors[0].cursor.file), "src/demo/util.es");
+        let (start, _) = errors[0].cursor.resolve(&program.sources, Columns::Scalars);
+        assert_eq!((errors[0].cursor.file, start.line_num), (util, 1));
+    }
 }
@@ -2,12 +2,13 @@ use logos::{Lexer, Logos};
 use utilities::{
     cursor::Cursor,
     message::{details, Message, MessageType::*},
-    MissingExpression,
+    source::FileId,
+    suggest::{did_you_mean, similar},
 };
 
 use crate::{
-    ast::{Expression, Parameter, Type, Argument},
-    token::{self, Token},
+    ast::{Argument, Codeblock, Expression, ImportedName, Parameter, Type},
+    token::{self, Token, KEYWORDS},
 };
 
 struct Parser<'a> {
@@ -18,23 +19,25 @@ struct Parser<'a> {
     cursor: Cursor,
     peek_cursor: Cursor,
     peek_slice: String,
+    /// Errors recovered from so far
+    messages: Vec<Message>,
+    /// How many blocks the current statement is in
+    depth: usize,
 }
 impl<'a> Parser<'a> {
     // MISC
 
-    fn new(lexer: Lexer<'a, Token>, file_name: &str, text: &str) -> Self {
+    fn new(lexer: Lexer<'a, Token>, file: FileId) -> Self {
         Self {
             lexer,
             current: Token::EoF,
             slice: String::new(),
             peek: Token::EoF,
-            cursor: Cursor::new(
-                file_name, text,
-            ),
-            peek_cursor: Cursor::new(
-                file_name, text,
-            ),
+            cursor: Cursor::new(file, 0..0),
+            peek_cursor: Cursor::new(file, 0..0),
             peek_slice: String::new(),
+            messages: Vec::new(),
+            depth: 0,
         }
     }
 
@@ -74,38 +77,6 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn suspect_identifier(&self) -> Result<Option<String>, Message> {
-        let ident = self.suspect(Token::Identifier)?;
-        if let None = ident {
-            return Ok(None);
-        }
-        Ok(Some(
-            self.slice.to_string(),
-        ))
-    }
-
-    fn suspect_and_advance(&mut self, token: Token) -> Result<Option<Token>, Message> {
-        let out = self.suspect(token)?;
-        match out {
-            | Some(out) => {
-                self.advance();
-                Ok(Some(out))
-            }
-            | None => Ok(None),
-        }
-    }
-
-    fn suspect_identifier_and_advance(&mut self) -> Result<Option<String>, Message> {
-        let out = self.suspect_identifier()?;
-        match out {
-            | Some(out) => {
-                self.advance();
-                Ok(Some(out))
-            }
-            | None => Ok(None),
-        }
-    }
-
     fn expect(&self, token: Token, error: Message) -> Result<Token, Message> {
         if self.current == token {
             Ok(self.current.clone())
@@ -167,7 +138,7 @@ impl<'a> Parser<'a> {
             | Token::Identifier => {
                 if self.peek == Token::Assign {
                     let name = self.slice.clone();
-                    let start = self.cursor.start.clone();
+                    let start = self.cursor.start;
 
                     self.advance();
                     self.advance();
@@ -180,50 +151,33 @@ impl<'a> Parser<'a> {
                         Expression::VariableAssign {
                             name,
                             value,
-                            cursor: self.cursor.clone_with_start(&start)
+                            cursor: self.cursor.clone_with_start(start)
                         }
                     )
                 }
                 else if self.peek == Token::LeftParen {
-                    let mut arguments: Vec<Argument> = Vec::new();
-
                     let name = self.slice.clone();
-                    let start = self.cursor.start.clone();
+                    let start = self.cursor.start;
 
                     self.advance();
                     self.advance();
 
-                    self.skip_whitespace();
+                    self.parse_call(name, start)
+                }
+                else if let (Token::Colon, Some(path)) = (&self.peek, self.namespaced_path()) {
+                    let name = format!(
+                        "{}:{}",
+                        self.slice, path
+                    );
+                    let start = self.cursor.start;
 
-                    loop {
-                        match self.current {
-                            | Token::RightParen => {
-                                self.advance();
-                                break;
-                            }
-                            | Token::Comma => {
-                                self.advance();
-                                self.skip_whitespace();
-                                continue;
-                            }
-                            _ => {
-                                arguments.push(
-                                    Argument {
-                                        expression: self.statement()?
-                                    }
-                                );
-                                self.skip_whitespace();
-                            }
-                        }
-                    }
+                    self.lexer.bump(path.len());
 
-                    Ok(
-                        Expression::FunctionCall {
-                            name,
-                            arguments,
-                            cursor: self.cursor.clone_with_start(&start)
-                        }
-                    )
+                    self.advance();
+                    self.advance();
+                    self.advance();
+
+                    self.parse_call(name, start)
                 }
                 else {
                     Ok(
@@ -271,6 +225,57 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// The resource location path following the ':' in `peek`, if a '(' comes
+    /// right after it, making a call like `namespace:path/to/function()`.
+    /// Paths may contain characters identifiers can't, so they're read from
+    /// the source directly.
+    fn namespaced_path(&self) -> Option<String> {
+        let remainder = self.lexer.remainder();
+        let path: String = remainder
+            .chars()
+            .take_while(|chr| matches!(chr, 'a'..='z' | '0'..='9' | '_' | '.' | '/' | '-'))
+            .collect();
+        if path.is_empty() || !remainder[path.len()..].starts_with('(') {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Parses the arguments of a call to `name`, starting after the '('.
+    fn parse_call(&mut self, name: String, start: usize) -> Result<Expression, Message> {
+        let mut arguments: Vec<Argument> = Vec::new();
+
+        self.skip_whitespace();
+
+        loop {
+            match self.current {
+                | Token::RightParen => break,
+                | Token::Comma => {
+                    self.advance();
+                    self.skip_whitespace();
+                    continue;
+                }
+                _ => {
+                    arguments.push(
+                        Argument {
+                            expression: self.statement()?
+                        }
+                    );
+                    self.advance();
+                    self.skip_whitespace();
+                }
+            }
+        }
+
+        Ok(
+            Expression::FunctionCall {
+                name,
+                arguments,
+                cursor: self.cursor.clone_with_start(start)
+            }
+        )
+    }
+
     fn math_expr_1(&mut self) -> Result<Expression, Message> {
         let mut left = self.atom()?;
 
@@ -334,7 +339,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_let(&mut self) -> Result<Expression, Message> {
-        let start_pos = self.cursor.start.clone();
+        let start_pos = self.cursor.start;
 
         self.advance();
 
@@ -352,7 +357,7 @@ impl<'a> Parser<'a> {
             | Token::Colon => {
                 self.advance();
                 variable_type = Some(Type {
-                    name: self.expect_identifier_and_advance(
+                    name: self.expect_identifier(
                         Message::error(
                             MissingMemberType,
                             details::MissingMemberType!("variable"),
@@ -360,6 +365,10 @@ impl<'a> Parser<'a> {
                         ),
                     )?,
                 });
+                // without a value, the type is the last token of the declaration
+                if self.peek == Token::Assign {
+                    self.advance();
+                }
             }
             | Token::Assign => variable_type = None,
             | _ => {
@@ -393,18 +402,13 @@ impl<'a> Parser<'a> {
                 } else {
                     None
                 },
-                cursor: Cursor {
-                    start: start_pos,
-                    end: self.cursor.end.clone(),
-                    file_name: self.cursor.file_name.clone(),
-                    text: self.cursor.text.clone(),
-                },
+                cursor: self.cursor.clone_with_start(start_pos),
             },
         )
     }
 
     fn parse_function(&mut self) -> Result<Expression, Message> {
-        let start_pos = self.cursor.start.clone();
+        let start_pos = self.cursor.start;
 
         self.skip_whitespace();
         self.advance();
@@ -555,6 +559,22 @@ impl<'a> Parser<'a> {
 
         // block
 
+        let body = self.parse_block()?;
+
+        // construction
+
+        Ok(
+            Expression::FunctionDeclaration {
+                name,
+                parameters,
+                return_type,
+                body,
+                cursor: self.cursor.clone_with_start(start_pos)
+            },
+        )
+    }
+
+    fn parse_block(&mut self) -> Result<Codeblock, Message> {
         self.expect_and_advance(
             Token::LeftBrace,
             Message::error(
@@ -564,60 +584,171 @@ impl<'a> Parser<'a> {
             ),
         )?;
 
-        self.skip_whitespace();
+        self.depth += 1;
+        let body = self.parse_statements(Token::RightBrace);
+        self.depth -= 1;
 
-        if self.current == Token::RightBrace {
-            return Ok(
-                Expression::FunctionDeclaration {
-                    name,
-                    parameters,
-                    return_type,
-                    body: Vec::new(),
-                    cursor: self.cursor.clone_with_start(&start_pos)
-                },
+        // the block is kept, as only its end is missing
+        if self.current != Token::RightBrace {
+            self.messages.push(
+                Message::error(
+                    MissingBlockClosure,
+                    details::MissingBlockClosure!(),
+                    self.cursor.clone(),
+                ),
             );
         }
 
-        let mut body: Vec<Expression> = Vec::new();
+        Ok(body)
+    }
 
-        loop {
-            body.push(self.statement()?);
-            self.advance();
-            self.skip_whitespace();
-            match self.current {
-                | Token::RightBrace => break,
-                | Token::NewLine => {
-                    self.advance();
-                    self.skip_whitespace();
-                    continue;
-                }
-                | _ => {
-                    return Err(
+    /// Parses statements up to `end` or the end of the file. A statement that
+    /// fails is reported and replaced by an `ErrorNode`, and parsing carries on
+    /// from the next one.
+    fn parse_statements(&mut self, end: Token) -> Codeblock {
+        let mut body: Codeblock = Vec::new();
+
+        self.skip_whitespace();
+
+        while self.current != end && self.current != Token::EoF {
+            let start = self.cursor.clone();
+            // a name followed by another or a string may be a misspelled keyword
+            let word = match (&self.current, &self.peek) {
+                | (Token::Identifier, Token::Identifier | Token::String) => Some(self.slice.clone()),
+                | _ => None,
+            };
+            let statement = self.statement().and_then(|statement| {
+                self.advance();
+                match self.current {
+                    | Token::NewLine => {
+                        self.skip_whitespace();
+                        Ok(statement)
+                    }
+                    | Token::EoF => Ok(statement),
+                    | _ if self.current == end => Ok(statement),
+                    | _ => Err(
                         Message::error(
                             MissingBlockSeparatorOrClosure,
                             details::MissingBlockSeparatorOrClosure!(),
                             self.cursor.clone(),
                         ),
-                    )
+                    ),
+                }
+            });
+
+            match statement {
+                | Ok(statement) => body.push(statement),
+                | Err(mut message) => {
+                    if let Some(word) = &word {
+                        if let Some(help) = did_you_mean(&similar(word, KEYWORDS)) {
+                            message = message.with_help(&format!(
+                                "'{}' isn't a keyword, {}",
+                                word, help
+                            ));
+                        }
+                    }
+                    body.push(Expression::ErrorNode(
+                        message.cursor.clone_with_start(start.start),
+                    ));
+                    self.messages.push(message);
+                    self.synchronize(&start);
                 }
             }
         }
 
-        // construction
+        body
+    }
+
+    /// Skips to where the next statement starts after an error: past the end
+    /// of the line, or to a keyword or the '}' closing the current block.
+    /// Nested blocks are skipped entirely.
+    fn synchronize(&mut self, start: &Cursor) {
+        // the failed statement must give up at least a token
+        if self.cursor.start == start.start && self.current != Token::EoF {
+            self.advance();
+        }
+
+        let mut depth = 0;
+        loop {
+            match self.current {
+                | Token::EoF => return,
+                | Token::LeftBrace => depth += 1,
+                | Token::RightBrace if depth > 0 => depth -= 1,
+                | Token::RightBrace => return,
+                | Token::NewLine if depth == 0 => {
+                    self.skip_whitespace();
+                    return;
+                }
+                | Token::Let | Token::Function | Token::Raw | Token::For | Token::Import | Token::Export
+                    if depth == 0 =>
+                {
+                    return
+                }
+                | _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    fn parse_for(&mut self) -> Result<Expression, Message> {
+        let start_pos = self.cursor.start;
+
+        self.advance();
+
+        let variable = self.expect_identifier_and_advance(
+            Message::error(
+                MissingMemberName,
+                details::MissingMemberName!("loop variable"),
+                self.cursor.clone(),
+            ),
+        )?;
+
+        self.expect_and_advance(
+            Token::In,
+            Message::error(
+                MissingKeyword,
+                details::MissingKeyword!("in"),
+                self.cursor.clone(),
+            ),
+        )?;
+
+        // range
+
+        let start = Box::new(self.math_expr_2()?);
+
+        self.advance();
+
+        self.expect_and_advance(
+            Token::Range,
+            Message::error(
+                MissingRangeSeparator,
+                details::MissingRangeSeparator!(),
+                self.cursor.clone(),
+            ),
+        )?;
+
+        let end = Box::new(self.math_expr_2()?);
+
+        self.advance();
+        self.skip_whitespace();
+
+        // block
+
+        let body = self.parse_block()?;
 
         Ok(
-            Expression::FunctionDeclaration {
-                name,
-                parameters,
-                return_type,
+            Expression::ForLoop {
+                variable,
+                start,
+                end,
                 body,
-                cursor: self.cursor.clone_with_start(&start_pos)
+                cursor: self.cursor.clone_with_start(start_pos),
             },
         )
     }
 
     fn parse_raw(&mut self) -> Result<Expression, Message> {
-        let start = self.cursor.start.clone();
+        let start = self.cursor.start;
 
         self.advance();
 
@@ -626,12 +757,7 @@ impl<'a> Parser<'a> {
             Ok(
                 Expression::RawCode {
                     string,
-                    cursor: Cursor {
-                        start,
-                        end: self.cursor.end.clone(),
-                        file_name: self.cursor.file_name.clone(),
-                        text: self.cursor.text.clone(),
-                    },
+                    cursor: self.cursor.clone_with_start(start),
                 },
             )
         } else {
@@ -645,53 +771,139 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn statement(&mut self) -> Result<Expression, Message> {
-        let out = match self.current {
-            | Token::Let => self.parse_let(),
-            | Token::Function => self.parse_function(),
-            | Token::Raw => self.parse_raw(),
-            | _ => self.math_expr_2(),
-        };
+    fn parse_import(&mut self) -> Result<Expression, Message> {
+        let start = self.cursor.start;
 
-        self.skip_whitespace();
+        self.advance();
 
-        out
-    }
+        self.expect_and_advance(
+            Token::LeftBrace,
+            Message::error(
+                MissingExpression,
+                details::MissingCharacter!('{'),
+                self.cursor.clone(),
+            ),
+        )?;
 
-    fn parse(&mut self) -> Result<Vec<Expression>, Message> {
-        let mut out: Vec<Expression> = Vec::new();
-        while self.current != Token::EoF {
-            out.push(self.statement()?);
-            self.advance();
+        let mut names: Vec<ImportedName> = Vec::new();
+
+        loop {
+            self.skip_whitespace();
             match self.current {
-                | Token::EoF => break,
-                | Token::NewLine => {
+                | Token::RightBrace => {
                     self.advance();
-                    self.skip_whitespace();
-                },
+                    break;
+                }
+                | Token::Comma => self.advance(),
+                | Token::Identifier => {
+                    names.push(
+                        ImportedName {
+                            name: self.slice.clone(),
+                            cursor: self.cursor.clone(),
+                        },
+                    );
+                    self.advance();
+                }
                 | _ => {
                     return Err(
                         Message::error(
-                            MissingBlockSeparatorOrClosure,
-                            details::MissingBlockSeparatorOrClosure!(),
+                            MissingMemberName,
+                            details::MissingMemberName!("imported function"),
                             self.cursor.clone(),
                         ),
                     )
                 }
             }
         }
-        Ok(out)
+
+        // `from` is only a keyword here, so it can still be used as a name
+        let error = Message::error(
+            MissingKeyword,
+            details::MissingKeyword!("from"),
+            self.cursor.clone(),
+        );
+        if self.expect_identifier_and_advance(error.clone())? != "from" {
+            return Err(error);
+        }
+
+        self.expect(
+            Token::String,
+            Message::error(
+                MissingExpression,
+                details::MissingSpecificExpression!("string"),
+                self.cursor.clone(),
+            ),
+        )?;
+
+        Ok(
+            Expression::Import {
+                names,
+                path: token::to_string(&self.slice),
+                cursor: self.cursor.clone_with_start(start),
+            },
+        )
+    }
+
+    fn parse_export(&mut self) -> Result<Expression, Message> {
+        let start = self.cursor.start;
+
+        self.advance();
+
+        self.expect(
+            Token::Function,
+            Message::error(
+                MissingKeyword,
+                details::MissingKeyword!("function"),
+                self.cursor.clone(),
+            ),
+        )?;
+
+        let declaration = Box::new(self.parse_function()?);
+
+        Ok(
+            Expression::Export {
+                declaration,
+                cursor: self.cursor.clone_with_start(start),
+            },
+        )
+    }
+
+    fn statement(&mut self) -> Result<Expression, Message> {
+        let out = match self.current {
+            | Token::Import | Token::Export if self.depth > 0 => Err(
+                Message::error(
+                    MisplacedDeclaration,
+                    details::MisplacedDeclaration!(match self.current {
+                        | Token::Import => "Import",
+                        | _ => "Export",
+                    }),
+                    self.cursor.clone(),
+                ),
+            ),
+            | Token::Import => self.parse_import(),
+            | Token::Export => self.parse_export(),
+            | Token::Let => self.parse_let(),
+            | Token::Function => self.parse_function(),
+            | Token::Raw => self.parse_raw(),
+            | Token::For => self.parse_for(),
+            | _ => self.math_expr_2(),
+        };
+
+        self.skip_whitespace();
+
+        out
+    }
+
+    fn parse(&mut self) -> Vec<Expression> {
+        self.parse_statements(Token::EoF)
     }
 }
 
-pub fn parse(text: impl ToString) -> Result<Vec<Expression>, Message> {
-    let text_string = text.to_string();
-    let text_str = text_string.as_str();
-    let mut parser = Parser::new(
-        token::Token::lexer(text_str),
-        "<stdin>",
-        text_str,
-    );
+/// Parses a whole file. Errors don't stop parsing, so the tree is returned along
+/// with every error found, with `ErrorNode`s where statements failed.
+pub fn parse(file: FileId, text: &str) -> (Vec<Expression>, Vec<Message>) {
+    let mut parser = Parser::new(token::Token::lexer(text), file);
     parser.init();
-    parser.parse()
+    let ast = parser.parse();
+    (ast, parser.messages)
 }
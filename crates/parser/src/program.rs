@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use utilities::{
+    message::Message,
+    source::{FileId, SourceMap},
+};
+
+use crate::{ast::Expression, parser::parse};
+
+/// A parsed file of a program.
+pub struct Module {
+    pub file: FileId,
+    pub ast: Vec<Expression>,
+    /// Syntax errors found in the file
+    pub messages: Vec<Message>,
+}
+
+/// Every file of a project, parsed into modules sharing one source map.
+#[derive(Default)]
+pub struct Program {
+    pub sources: SourceMap,
+    pub modules: Vec<Module>,
+}
+impl Program {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses many files into a program, in order.
+    pub fn parse<N, T>(files: impl IntoIterator<Item = (N, T)>) -> Self
+    where
+        N: Into<Arc<str>>,
+        T: Into<Arc<str>>,
+    {
+        let mut program = Self::new();
+        for (name, text) in files {
+            program.add(name, text);
+        }
+        program
+    }
+
+    /// Adds a file to the program and parses it.
+    pub fn add(&mut self, name: impl Into<Arc<str>>, text: impl Into<Arc<str>>) -> FileId {
+        let file = self.sources.add(name, text);
+        let (ast, messages) = parse(file, self.sources.text(file));
+        self.modules.push(Module {
+            file,
+            ast,
+            messages,
+        });
+        file
+    }
+
+    pub fn module(&self, file: FileId) -> Option<&Module> {
+        self.modules
+            .iter()
+            .find(|module| module.file == file)
+    }
+
+    /// The syntax errors of every module.
+    pub fn messages(&self) -> impl Iterator<Item = &Message> {
+        self.modules
+            .iter()
+            .flat_map(|module| &module.messages)
+    }
+}
@@ -14,6 +14,9 @@ pub fn to_string(slice: &impl ToString) -> String {
         .collect()
 }
 
+/// Words that can't be used as names.
+pub const KEYWORDS: [&str; 7] = ["function", "let", "raw", "for", "in", "import", "export"];
+
 #[derive(Logos, Debug, Clone, PartialEq)]
 pub enum Token {
     #[token("+")]
@@ -64,6 +67,16 @@ pub enum Token {
     Let,
     #[token("raw")]
     Raw,
+    #[token("for")]
+    For,
+    #[token("in")]
+    In,
+    #[token("import")]
+    Import,
+    #[token("export")]
+    Export,
+    #[token("..")]
+    Range,
     /*#[token("while")]
     While,
     #[token("if")]
@@ -78,7 +91,7 @@ pub enum Token {
     Or,
     #[token("not")]
     Not,*/
-    #[regex(r##"[a-zA-Z][a-zA-Z0-9]*"##)]
+    #[regex(r##"[a-zA-Z_][a-zA-Z0-9_]*"##)]
     Identifier,
     #[regex(r##""(?:\\"|[^"])*""##)]
     String,
@@ -40,4 +40,4 @@ macro_rules! Cyan {
     };
 }
 
-pub use {Cyan, /*Yellow, LightYellow,*/ Grey, LightRed, Red};
+pub use {Cyan, Grey, LightRed, LightYellow, Red, Yellow};
@@ -1,12 +1,191 @@
 pub mod color;
 pub mod cursor;
 pub mod message;
+pub mod source;
+pub mod suggest;
 
 #[cfg(test)]
 mod tests {
+    use crate::cursor::Cursor;
+    use crate::message::{Ansi, Html, Message, MessageType, Plain};
+    use crate::source::{Columns, SourceMap};
+    use crate::suggest::{did_you_mean, edit_distance, similar};
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn every_code_is_explained() {
+        for message_type in MessageType::ALL {
+            let explanation = message_type.explanation();
+            assert!(
+                explanation.starts_with(&format!(
+                    "# {}: {}\n",
+                    message_type.code(),
+                    message_type.name()
+                )),
+                "{} has no heading",
+                message_type.code()
+            );
+            for section in ["## Erroneous example\n\n```es\n", "## Corrected example\n\n```es\n"] {
+                assert!(
+                    explanation.contains(section),
+                    "{} has no '{}'",
+                    message_type.code(),
+                    section.lines().next().unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn similar_names_are_suggested() {
+        assert_eq!(edit_distance("fucntion", "function"), 1);
+        assert_eq!(edit_distance("count", "amount"), 2);
+        assert_eq!(similar("cuont", ["count", "amount", "c", "cuont"]), ["count"]);
+        assert!(similar("x", ["count"]).is_empty());
+        assert_eq!(did_you_mean(&["a"]).unwrap(), "did you mean 'a'?");
+        assert_eq!(did_you_mean(&["a", "b", "c"]).unwrap(), "did you mean 'a', 'b' or 'c'?");
+        assert_eq!(did_you_mean(&[]), None);
+    }
+
+    #[test]
+    fn messages_convert_to_json() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.es", "let a = 1\nlet b = c\n");
+        let cursor = Cursor::new(file, 18..19);
+        let json = Message::error(MessageType::UnknownMember, "Variable 'c' is not declared in this scope", cursor).to_json(&sources, Columns::Scalars);
+
+        assert_eq!(
+            json.dump(),
+            "{\"code\":\"ES103E\",\"severity\":\"error\",\"title\":\"Unknown member\",\
+             \"message\":\"Variable 'c' is not declared in this scope\",\"file\":\"main.es\",\
+             \"start\":{\"line\":2,\"column\":9},\"end\":{\"line\":2,\"column\":10},\"notes\":[],\"help\":[],\"labels\":[]}"
+        );
+    }
+
+    #[test]
+    fn details_hold_no_colors() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.es", "let a = \"hi\"\n");
+        let details = crate::message::details::TypeMismatch!("int", "string").to_string();
+        let message = Message::error(MessageType::TypeMismatch, &details, Cursor::new(file, 8..12));
+
+        assert_eq!(message.details, "Expected value of type int, got string");
+        for rendered in [
+            message.render(&Plain, &sources),
+            message.render(&Html, &sources),
+            message.to_json(&sources, Columns::Scalars).dump(),
+        ] {
+            assert!(!rendered.contains('\x1b'), "{}", rendered);
+        }
+    }
+
+    /// Drops the colors of rendered text.
+    fn plain(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(chr) = chars.next() {
+            if chr == '\x1b' {
+                chars.by_ref().find(|&chr| chr == 'm');
+            } else {
+                plain.push(chr);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn labels_and_spans_over_lines_are_drawn() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.es", "let i = 1\nfor i in 0..2 {\n    raw \"say\"\n}\nlet a = 2\n");
+        let message = Message::error(MessageType::MemberRedeclaration, "Variable 'i' had already been declared", Cursor::new(file, 10..41))
+            .with_label(Cursor::new(file, 0..9), "first declared here")
+            .with_note("a note")
+            .with_help("some help");
+
+        assert_eq!(
+            plain(&message.render(&Ansi, &sources)),
+            "Error ES104E: Member redeclaration\n\
+             \x20  ╭─[main.es:2:1]\n\
+             \x20  │\n\
+             \x201 │   let i = 1\n\
+             \x20  ·   ────┬────\n\
+             \x20  ·       ╰────── first declared here\n\
+             \x202 │ ╭ for i in 0..2 {\n\
+             \x20  ⋮ │ \n\
+             \x204 │ │ }\n\
+             \x20  · ╰── Variable 'i' had already been declared\n\
+             \x20  = note: a note\n\
+             \x20  = help: some help\n\
+             ───╯"
+        );
+    }
+
+    #[test]
+    fn renderers_share_the_layout() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.es", "let a = b < 1\n");
+        let message = Message::error(MessageType::UnknownMember, "Variable 'b' is not declared in this scope", Cursor::new(file, 8..9));
+
+        assert_eq!(
+            message.render(&Plain, &sources),
+            "Error ES103E: Unknown member\n\
+             \x20  ,-[main.es:1:9]\n\
+             \x20  |\n\
+             \x201 | let a = b < 1\n\
+             \x20  :         +\n\
+             \x20  :         `-- Variable 'b' is not declared in this scope\n\
+             ---'"
+        );
+
+        let html = message.render(&Html, &sources);
+        assert!(html.starts_with("<pre class=\"es-diagnostic\"><span class=\"es-header-error\">Error ES103E: </span>"));
+        assert!(html.contains("<span class=\"es-span-error\">b</span><span class=\"es-frame\"> &lt; 1</span>"));
+        assert!(html.ends_with("</pre>"));
+    }
+
+    #[test]
+    fn cursors_resolve_through_the_source_map() {
+        let mut sources = SourceMap::new();
+        let main = sources.add("main.es", "let a = 1\n");
+        let util = sources.add("util.es", "let é = 2\nlet b = é\n");
+        assert_eq!(sources.name(util), "util.es");
+
+        // a new line token stays on the line it ends
+        let (start, end) = Cursor::new(main, 9..10).resolve(&sources, Columns::Scalars);
+        assert_eq!((start.line_num, start.col, end.line_num, end.col), (0, 9, 0, 9));
+
+        let (start, end) = Cursor::new(util, 19..21).resolve(&sources, Columns::Scalars);
+        assert_eq!((start.line_num, start.col, end.line_num, end.col), (1, 8, 1, 9));
+
+        let message = Message::error(MessageType::UnknownMember, "unknown", Cursor::new(util, 19..21));
+        assert!(message.render(&Plain, &sources).contains("[util.es:2:9]"));
+    }
+
+    #[test]
+    fn columns_count_scalars_or_utf16() {
+        let mut sources = SourceMap::new();
+        let text = "let a = 1\n\nraw \"héllo 😀\" b\n";
+        let file = sources.add("main.es", text);
+        let source = sources.get(file);
+
+        assert_eq!(source.line(1), Some(""));
+        assert_eq!(source.line(2), Some("raw \"héllo 😀\" b"));
+        assert_eq!(source.line(4), None);
+        assert_eq!(source.line_num(text.len()), 3);
+
+        let b = text.rfind('b').unwrap();
+        assert_eq!(sources.position(file, b, Columns::Scalars).col, 14);
+        assert_eq!(sources.position(file, b, Columns::Utf16).col, 15);
+        assert_eq!(sources.position(file, text.len() + 5, Columns::Scalars).line_num, 3);
+
+        let message = Message::error(MessageType::UnknownMember, "unknown", Cursor::new(file, b..b + 1));
+        assert!(message
+            .render(&Plain, &sources)
+            .contains(" 3 | raw \"héllo 😀\" b\n   :               +\n"));
+    }
 }
@@ -1,10 +1,11 @@
-use ansi_term::ANSIString;
-use ansi_term::ANSIStrings;
-use ansi_term::Color::{self, Fixed, White};
+use ansi_term::Color::{Fixed, White};
+use json::{object, JsonValue};
 use std::cmp::{max, min};
+use std::iter::once;
 
 use crate::color::*;
-use crate::cursor::Cursor;
+use crate::cursor::{Cursor, Position};
+use crate::source::{Columns, FileId, SourceMap};
 
 pub mod details {
 
@@ -135,6 +136,13 @@ pub mod details {
         };
     }
 
+    #[macro_export]
+    macro_rules! MissingBlockClosure {
+        () => {
+            "Expected '}' to close the block"
+        };
+    }
+
     #[macro_export]
     macro_rules! MissingBlockSeparatorOrClosure {
         () => {
@@ -142,6 +150,24 @@ pub mod details {
         };
     }
 
+    #[macro_export]
+    macro_rules! MissingKeyword {
+        ($keyword: tt) => {
+            format!(
+                "Expected keyword '{}'",
+                $keyword
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! MissingRangeSeparator {
+        () => {
+            "Expected '..' to separate the range bounds"
+        };
+    }
+
     #[macro_export]
     macro_rules! UnknownType {
         ($type_name: expr) => {
@@ -169,8 +195,7 @@ pub mod details {
         ($expected: tt, $got: expr) => {
             format!(
                 "Expected value of type {}, got {}",
-                Cyan!().paint($expected),
-                Cyan!().paint($got)
+                $expected, $got
             )
             .as_str()
         };
@@ -198,16 +223,141 @@ pub mod details {
         };
     }
 
+    #[macro_export]
+    macro_rules! UnboundedRecursion {
+        ($function_name: expr) => {
+            format!(
+                "Function '{}' calls itself with nothing to stop it, so it runs until maxCommandChainLength is hit",
+                $function_name
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! UnsupportedFeature {
+        ($feature: expr, $since: expr, $target: expr) => {
+            format!(
+                "{} require Minecraft {} or newer, but the target is {}",
+                $feature, $since, $target
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! UnusedVariable {
+        ($name: expr) => {
+            format!(
+                "Variable '{}' is never read, prefix it with '_' if that is intended",
+                $name
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! UnusedFunction {
+        ($name: expr) => {
+            format!(
+                "Function '{}' is never called",
+                $name
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! Shadowing {
+        ($name: expr) => {
+            format!(
+                "Variable '{}' hides a variable of an enclosing block",
+                $name
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! UndefinedRead {
+        ($name: expr) => {
+            format!(
+                "Variable '{}' is read before it is given a value",
+                $name
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! UnreachableCode {
+        ($reason: expr) => {
+            format!(
+                "This code never runs, as {}",
+                $reason
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! MisplacedDeclaration {
+        ($declaration_type: expr) => {
+            format!(
+                "{} declarations are only allowed at the top level of a file",
+                $declaration_type
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! UnknownModule {
+        ($path: expr) => {
+            format!(
+                "Module '{}' doesn't exist in this namespace",
+                $path
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! ImportCycle {
+        ($cycle: expr) => {
+            format!(
+                "Modules import each other in a cycle: {}",
+                $cycle
+            )
+            .as_str()
+        };
+    }
+
+    #[macro_export]
+    macro_rules! UnknownExport {
+        ($module: expr, $name: expr) => {
+            format!(
+                "Module '{}' doesn't export '{}'",
+                $module, $name
+            )
+            .as_str()
+        };
+    }
+
     pub use {
-        IllegalCharacter, IntegerBoundsExceeded, MemberRedeclaration, MissingBlock,
+        MisplacedDeclaration, UnknownModule, ImportCycle, UnknownExport,
+        UnusedVariable, UnusedFunction, Shadowing, UndefinedRead, UnreachableCode,
+        UnboundedRecursion, UnsupportedFeature, IllegalCharacter, IntegerBoundsExceeded, MemberRedeclaration, MissingBlock,
+        MissingBlockClosure,
         MissingBlockSeparatorOrClosure, MissingCase, MissingCaseClosure,
-        MissingCaseSeparatorOrClosure, MissingCharacter, MissingExpression,
+        MissingCaseSeparatorOrClosure, MissingCharacter, MissingExpression, MissingKeyword,
         MissingMemberDeclaration, MissingMemberName, MissingMemberType, MissingMemberTypeColon,
-        MissingMemberTypeOrValueAssignment, TypeMismatch, UnknownMember, UnknownType, MissingSpecificExpression
+        MissingMemberTypeOrValueAssignment, MissingRangeSeparator, TypeMismatch, UnknownMember,
+        UnknownType, MissingSpecificExpression
     };
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum MessageType {
     IllegalCharacter,
     MissingExpression,
@@ -221,13 +371,65 @@ pub enum MessageType {
     MissingBlock,
     MissingBlockClosure,
     MissingBlockSeparatorOrClosure,
+    MissingKeyword,
+    MissingRangeSeparator,
+    MisplacedDeclaration,
     UnknownType,
     IntegerBoundsExceeded,
     TypeMismatch,
     UnknownMember,
     MemberRedeclaration,
+    UnboundedRecursion,
+    UnsupportedFeature,
+    UnusedVariable,
+    UnusedFunction,
+    Shadowing,
+    UndefinedRead,
+    UnreachableCode,
+    UnknownModule,
+    ImportCycle,
+    UnknownExport,
 }
 impl MessageType {
+    pub const ALL: [MessageType; 30] = [
+        MessageType::IllegalCharacter,
+        MessageType::MissingExpression,
+        MessageType::MissingMemberDeclaration,
+        MessageType::MissingMemberName,
+        MessageType::MissingMemberType,
+        MessageType::MissingMemberTypeOrValueAssignment,
+        MessageType::MissingCase,
+        MessageType::MissingCaseClosure,
+        MessageType::MissingCaseSeparatorOrClosure,
+        MessageType::MissingBlock,
+        MessageType::MissingBlockClosure,
+        MessageType::MissingBlockSeparatorOrClosure,
+        MessageType::MissingKeyword,
+        MessageType::MissingRangeSeparator,
+        MessageType::MisplacedDeclaration,
+        MessageType::UnknownType,
+        MessageType::IntegerBoundsExceeded,
+        MessageType::TypeMismatch,
+        MessageType::UnknownMember,
+        MessageType::MemberRedeclaration,
+        MessageType::UnboundedRecursion,
+        MessageType::UnsupportedFeature,
+        MessageType::UnusedVariable,
+        MessageType::UnusedFunction,
+        MessageType::Shadowing,
+        MessageType::UndefinedRead,
+        MessageType::UnreachableCode,
+        MessageType::UnknownModule,
+        MessageType::ImportCycle,
+        MessageType::UnknownExport,
+    ];
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|message_type| message_type.code() == code)
+    }
+
     pub fn parameters(
         &self,
     ) -> (
@@ -308,6 +510,21 @@ impl MessageType {
                 "ES011E",
                 "Missing block separator or closure",
             ),
+            | MessageType::MissingKeyword => (
+                true,
+                "ES012E",
+                "Missing keyword",
+            ),
+            | MessageType::MissingRangeSeparator => (
+                true,
+                "ES013E",
+                "Missing range separator",
+            ),
+            | MessageType::MisplacedDeclaration => (
+                true,
+                "ES014E",
+                "Misplaced declaration",
+            ),
             | MessageType::UnknownType => (
                 true,
                 "ES100E",
@@ -333,9 +550,60 @@ impl MessageType {
                 "ES104E",
                 "Member redeclaration",
             ),
+            | MessageType::UnboundedRecursion => (
+                false,
+                "ES105W",
+                "Unbounded recursion",
+            ),
+            | MessageType::UnsupportedFeature => (
+                true,
+                "ES106E",
+                "Unsupported feature",
+            ),
+            | MessageType::UnusedVariable => (
+                false,
+                "ES107W",
+                "Unused variable",
+            ),
+            | MessageType::UnusedFunction => (
+                false,
+                "ES108W",
+                "Unused function",
+            ),
+            | MessageType::Shadowing => (
+                false,
+                "ES109W",
+                "Shadowed variable",
+            ),
+            | MessageType::UndefinedRead => (
+                false,
+                "ES110W",
+                "Read of an undefined variable",
+            ),
+            | MessageType::UnreachableCode => (
+                false,
+                "ES111W",
+                "Unreachable code",
+            ),
+            | MessageType::UnknownModule => (
+                true,
+                "ES200E",
+                "Unknown module",
+            ),
+            | MessageType::ImportCycle => (
+                true,
+                "ES201E",
+                "Import cycle",
+            ),
+            | MessageType::UnknownExport => (
+                true,
+                "ES202E",
+                "Unknown export",
+            ),
         }
     }
 
+    /// Whether messages of this type are errors unless configured otherwise.
     pub fn is_error(&self) -> bool {
         self.parameters().0
     }
@@ -347,154 +615,668 @@ impl MessageType {
     pub fn name(&self) -> &'static str {
         self.parameters().2
     }
+
+    /// Long-form description of the code, with an erroneous and a corrected
+    /// example, as printed by `esc explain`.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            | MessageType::IllegalCharacter => include_str!("../explanations/ES000E.md"),
+            | MessageType::MissingExpression => include_str!("../explanations/ES001E.md"),
+            | MessageType::MissingMemberDeclaration => include_str!("../explanations/ES002E.md"),
+            | MessageType::MissingMemberName => include_str!("../explanations/ES003E.md"),
+            | MessageType::MissingMemberType => include_str!("../explanations/ES004E.md"),
+            | MessageType::MissingMemberTypeOrValueAssignment => include_str!("../explanations/ES005E.md"),
+            | MessageType::MissingCase => include_str!("../explanations/ES006E.md"),
+            | MessageType::MissingCaseClosure => include_str!("../explanations/ES007E.md"),
+            | MessageType::MissingCaseSeparatorOrClosure => include_str!("../explanations/ES008E.md"),
+            | MessageType::MissingBlock => include_str!("../explanations/ES009E.md"),
+            | MessageType::MissingBlockClosure => include_str!("../explanations/ES010E.md"),
+            | MessageType::MissingBlockSeparatorOrClosure => include_str!("../explanations/ES011E.md"),
+            | MessageType::MissingKeyword => include_str!("../explanations/ES012E.md"),
+            | MessageType::MissingRangeSeparator => include_str!("../explanations/ES013E.md"),
+            | MessageType::MisplacedDeclaration => include_str!("../explanations/ES014E.md"),
+            | MessageType::UnknownType => include_str!("../explanations/ES100E.md"),
+            | MessageType::IntegerBoundsExceeded => include_str!("../explanations/ES101E.md"),
+            | MessageType::TypeMismatch => include_str!("../explanations/ES102E.md"),
+            | MessageType::UnknownMember => include_str!("../explanations/ES103E.md"),
+            | MessageType::MemberRedeclaration => include_str!("../explanations/ES104E.md"),
+            | MessageType::UnboundedRecursion => include_str!("../explanations/ES105W.md"),
+            | MessageType::UnsupportedFeature => include_str!("../explanations/ES106E.md"),
+            | MessageType::UnusedVariable => include_str!("../explanations/ES107W.md"),
+            | MessageType::UnusedFunction => include_str!("../explanations/ES108W.md"),
+            | MessageType::Shadowing => include_str!("../explanations/ES109W.md"),
+            | MessageType::UndefinedRead => include_str!("../explanations/ES110W.md"),
+            | MessageType::UnreachableCode => include_str!("../explanations/ES111W.md"),
+            | MessageType::UnknownModule => include_str!("../explanations/ES200E.md"),
+            | MessageType::ImportCycle => include_str!("../explanations/ES201E.md"),
+            | MessageType::UnknownExport => include_str!("../explanations/ES202E.md"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A span shown along with the main one, like an earlier declaration.
+#[derive(Clone)]
+pub struct Label {
+    pub cursor: Cursor,
+    pub text: String,
 }
 
 #[derive(Clone)]
 pub struct Message {
     pub message_type: MessageType,
+    pub severity: Severity,
     pub details: String,
     pub cursor: Cursor,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
 }
 
-impl Message {
-    fn color(&self) -> Color {
-        match self.message_type.is_error() {
-            | true => Red!(),
-            | false => todo!(),
-        }
+/// Which span a part of a diagnostic belongs to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    /// The span the message is about
+    Primary(Severity),
+    /// The span of a label
+    Secondary,
+}
+
+/// What a piece of a rendered diagnostic is, for renderers to style it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Text shown as is, like the details of the message
+    Text,
+    /// The severity and code heading the message
+    Header(Severity),
+    /// Line numbers, the frame, and source code outside of spans
+    Frame,
+    /// The file and position a snippet is from
+    Location,
+    /// "note: " and "help: "
+    Kind,
+    /// Source code inside a span
+    Span(Tone),
+    /// Underlines, and the margins of spans over several lines
+    Marker(Tone),
+}
+
+/// Characters the frame of a diagnostic is drawn with.
+pub struct Charset {
+    pub horizontal: char,
+    pub vertical: char,
+    pub top_left: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+    /// Joins the frame of another file to the first one
+    pub branch: char,
+    /// Where the pointer to a label leaves its underline
+    pub pointer: char,
+    /// The frame next to underlines
+    pub dotted: char,
+    /// The frame next to lines left out of a snippet
+    pub gap: char,
+}
+
+pub const UNICODE: Charset = Charset {
+    horizontal: '─',
+    vertical: '│',
+    top_left: '╭',
+    bottom_left: '╰',
+    bottom_right: '╯',
+    branch: '├',
+    pointer: '┬',
+    dotted: '·',
+    gap: '⋮',
+};
+
+pub const ASCII: Charset = Charset {
+    horizontal: '-',
+    vertical: '|',
+    top_left: ',',
+    bottom_left: '`',
+    bottom_right: '\'',
+    branch: '|',
+    pointer: '+',
+    dotted: ':',
+    gap: '.',
+};
+
+/// Turns diagnostics into text for an output, like a terminal or a web page.
+pub trait Renderer {
+    fn charset(&self) -> &Charset;
+
+    /// `text` styled as a piece of the given kind, escaped if the output needs
+    /// it.
+    fn paint(&self, text: &str, style: Style) -> String;
+
+    /// Wraps a whole rendered message.
+    fn finish(&self, message: String) -> String {
+        message
     }
+}
 
-    fn light_color(&self) -> Color {
-        match self.message_type.is_error() {
-            | true => LightRed!(),
-            | false => todo!(),
+/// Colors and box-drawing characters, for terminals.
+pub struct Ansi;
+impl Renderer for Ansi {
+    fn charset(&self) -> &Charset {
+        &UNICODE
+    }
+
+    fn paint(&self, text: &str, style: Style) -> String {
+        let tone_color = |tone: Tone| match tone {
+            | Tone::Primary(Severity::Error) => LightRed!(),
+            | Tone::Primary(Severity::Warning) => LightYellow!(),
+            | Tone::Secondary => Cyan!(),
+        };
+        let style = match style {
+            | Style::Text => return text.to_string(),
+            | Style::Header(Severity::Error) => Red!().bold(),
+            | Style::Header(Severity::Warning) => Yellow!().bold(),
+            | Style::Frame => Grey!().normal(),
+            | Style::Location => White.normal(),
+            | Style::Kind => White.bold(),
+            | Style::Span(tone) => tone_color(tone).bold(),
+            | Style::Marker(tone) => tone_color(tone).normal(),
+        };
+        style.paint(text).to_string()
+    }
+}
+
+/// ASCII without colors, for logs and consoles that can't show more.
+pub struct Plain;
+impl Renderer for Plain {
+    fn charset(&self) -> &Charset {
+        &ASCII
+    }
+
+    fn paint(&self, text: &str, _: Style) -> String {
+        text.to_string()
+    }
+}
+
+/// A `<pre>` element with a class on every styled piece, named like
+/// `es-marker-error`, for pages to color.
+pub struct Html;
+impl Renderer for Html {
+    fn charset(&self) -> &Charset {
+        &UNICODE
+    }
+
+    fn paint(&self, text: &str, style: Style) -> String {
+        let text = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let tone_name = |tone: Tone| match tone {
+            | Tone::Primary(Severity::Error) => "error",
+            | Tone::Primary(Severity::Warning) => "warning",
+            | Tone::Secondary => "label",
+        };
+        let class = match style {
+            | Style::Text => return text,
+            | Style::Header(severity) => format!(
+                "header-{}",
+                tone_name(Tone::Primary(severity))
+            ),
+            | Style::Frame => "frame".to_string(),
+            | Style::Location => "location".to_string(),
+            | Style::Kind => "kind".to_string(),
+            | Style::Span(tone) => format!(
+                "span-{}",
+                tone_name(tone)
+            ),
+            | Style::Marker(tone) => format!(
+                "marker-{}",
+                tone_name(tone)
+            ),
+        };
+        match text.is_empty() {
+            | true => text,
+            | false => format!(
+                "<span class=\"es-{}\">{}</span>",
+                class, text
+            ),
         }
     }
 
-    fn header(&self) -> String {
-        if self.message_type.is_error() {
-            return self
-                .color()
-                .bold()
-                .paint(format!(
-                    "Error {}: ",
-                    self.message_type.code()
-                ))
-                .to_string();
+    fn finish(&self, message: String) -> String {
+        format!(
+            "<pre class=\"es-diagnostic\">{}</pre>",
+            message
+        )
+    }
+}
+
+/// A span to draw, along with its text.
+struct Annotation<'a> {
+    file: FileId,
+    start: Position,
+    end: Position,
+    text: &'a str,
+    tone: Tone,
+}
+impl Annotation<'_> {
+    fn is_multiline(&self) -> bool {
+        self.end.line_num > self.start.line_num
+    }
+
+    /// Whether the span is on a line, even partly.
+    fn spans(&self, line_num: usize) -> bool {
+        self.start.line_num <= line_num && line_num <= self.end.line_num
+    }
+
+    fn covers(&self, line_num: usize, col: usize) -> bool {
+        let (start, end) = (&self.start, &self.end);
+        (line_num > start.line_num || (line_num == start.line_num && col >= start.col))
+            && (line_num < end.line_num || (line_num == end.line_num && col < end.col))
+    }
+}
+
+fn paint_run(renderer: &dyn Renderer, tone: Option<Tone>, text: &str) -> String {
+    match tone {
+        | Some(tone) => renderer.paint(text, Style::Span(tone)),
+        | None => renderer.paint(text, Style::Frame),
+    }
+}
+
+/// An underline of `len` columns from `col`, and a row pointing from its
+/// middle to `text`.
+fn underline(renderer: &dyn Renderer, col: usize, len: usize, text: &str, tone: Tone) -> [String; 2] {
+    let chars = renderer.charset();
+    let horizontal = chars.horizontal.to_string();
+    let middle: usize = ((len / 2) as f64 + 0.25) as usize;
+    let line = format!(
+        "{}{}{}{}",
+        " ".repeat(col),
+        horizontal.repeat(middle),
+        chars.pointer,
+        horizontal.repeat(len.saturating_sub(middle + 1))
+    );
+    let pointer = format!(
+        "{}{}{}{} ",
+        " ".repeat(col + middle),
+        chars.bottom_left,
+        chars.horizontal,
+        horizontal.repeat(len - middle)
+    );
+    [
+        renderer.paint(&line, Style::Marker(tone)),
+        renderer.paint(&pointer, Style::Marker(tone)) + &renderer.paint(text, Style::Text),
+    ]
+}
+
+impl Message {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    fn header(&self, renderer: &dyn Renderer) -> String {
+        let severity = match self.is_error() {
+            | true => "Error",
+            | false => "Warning",
+        };
+        renderer.paint(
+            &format!(
+                "{} {}: ",
+                severity,
+                self.message_type.code()
+            ),
+            Style::Header(self.severity),
+        )
+    }
+
+    fn file_name(renderer: &dyn Renderer, sources: &SourceMap, annotation: &Annotation) -> String {
+        renderer.paint("[", Style::Frame)
+            + &renderer.paint(
+                &format!(
+                    "{}:{}:{}",
+                    sources.name(annotation.file),
+                    annotation.start.line_num + 1,
+                    annotation.start.col + 1
+                ),
+                Style::Location,
+            )
+            + &renderer.paint("]", Style::Frame)
+    }
+
+    /// The main span followed by the labels, grouped by file.
+    fn annotations(&self, sources: &SourceMap) -> Vec<Vec<Annotation<'_>>> {
+        let annotation = |cursor: &Cursor, text, tone| {
+            let (start, end) = cursor.resolve(sources, Columns::Scalars);
+            Annotation {
+                file: cursor.file,
+                start,
+                end,
+                text,
+                tone,
+            }
+        };
+        let main = annotation(&self.cursor, &self.details, Tone::Primary(self.severity));
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| annotation(&label.cursor, &label.text, Tone::Secondary));
+
+        let mut groups: Vec<Vec<Annotation>> = Vec::new();
+        for annotation in once(main).chain(labels) {
+            match groups
+                .iter_mut()
+                .find(|group| group[0].file == annotation.file)
+            {
+                | Some(group) => group.push(annotation),
+                | None => groups.push(vec![annotation]),
+            }
         }
-        panic!();
-    }
-
-    fn file_name(&self) -> String {
-        let strings: &[ANSIString<'static>] = &[
-            Grey!().paint("["),
-            White.paint(format!(
-                "{}:{}:{}",
-                self.cursor.file_name, self.cursor.start.line_num, self.cursor.start.col
-            )),
-            Grey!().paint("]"),
-        ];
-        return ANSIStrings(strings).to_string();
-    }
-
-    fn line(&self) -> String {
-        let line = self
-            .cursor
-            .text
-            .split('\n')
-            .nth(self.cursor.start.line_num)
-            .unwrap()
-            .to_string();
-        let first: String = line.chars().take(self.cursor.start.col).collect();
-        let err: String = line
-            .chars()
-            .skip(self.cursor.start.col)
-            .take(self.cursor.end.col - self.cursor.start.col)
+        groups
+    }
+
+    /// The lines of a file the annotations are on. Spans on a single line are
+    /// underlined below it, while those covering several lines are drawn in a
+    /// margin left of the source and closed below their last line.
+    fn snippet(renderer: &dyn Renderer, sources: &SourceMap, annotations: &[Annotation], padding: &str) -> String {
+        let chars = renderer.charset();
+        let source = sources.get(annotations[0].file);
+        let multiline: Vec<&Annotation> = annotations
+            .iter()
+            .filter(|annotation| annotation.is_multiline())
             .collect();
-        let last: String = line.chars().skip(self.cursor.end.col).collect();
-        let strings: &[ANSIString<'static>] = &[
-            Grey!().paint(first),
-            self.light_color().bold().paint(err),
-            Grey!().paint(last),
-        ];
-        return ANSIStrings(strings).to_string();
-    }
-
-    fn content(&self) -> Vec<String> {
-        let mut result: Vec<String> = Vec::new();
-        let error_len = self.cursor.end.col - self.cursor.start.col;
-        let middle: usize = ((error_len / 2) as f64 + 0.25) as usize;
-        let mut underline = String::new();
-        for _ in 0..self.cursor.start.col {
-            underline += " ";
-        }
-        for _ in 0..middle {
-            underline += "─";
-        }
-        underline += "┬";
-        for _ in middle + 1..error_len {
-            underline += "─";
-        }
-        result.push(self.light_color().paint(underline).to_string());
-        let mut error_line = String::new();
-        for _ in 0..self.cursor.start.col + middle {
-            error_line += " ";
-        }
-        error_line += "╰─";
-        for _ in 0..error_len - middle {
-            error_line += "─";
+
+        let mut shown: Vec<usize> = annotations
+            .iter()
+            .flat_map(|annotation| [annotation.start.line_num, annotation.end.line_num])
+            .collect();
+        shown.sort();
+        shown.dedup();
+
+        // the margin of a row, marking the spans that are open at it
+        let margin = |open: &dyn Fn(&Annotation) -> char| {
+            let mut margin = String::new();
+            for annotation in &multiline {
+                margin += &renderer.paint(
+                    &open(annotation).to_string(),
+                    Style::Marker(annotation.tone),
+                );
+            }
+            if !multiline.is_empty() {
+                margin += " ";
+            }
+            margin
+        };
+        let number_width = padding.len() - 2;
+        let mut output = String::new();
+
+        for (index, &line_num) in shown.iter().enumerate() {
+            if index > 0 && line_num > shown[index - 1] + 1 {
+                output += &renderer.paint(
+                    &format!(
+                        "{}{} ",
+                        padding, chars.gap
+                    ),
+                    Style::Frame,
+                );
+                output += &margin(&|annotation| match annotation.spans(line_num - 1) {
+                    | true => chars.vertical,
+                    | false => ' ',
+                });
+                output += "\n";
+            }
+
+            // the source line, with the spans painted
+            output += &renderer.paint(
+                &format!(
+                    " {:>width$} {} ",
+                    line_num + 1,
+                    chars.vertical,
+                    width = number_width
+                ),
+                Style::Frame,
+            );
+            output += &margin(&|annotation| {
+                if annotation.start.line_num == line_num {
+                    chars.top_left
+                } else if annotation.spans(line_num) {
+                    chars.vertical
+                } else {
+                    ' '
+                }
+            });
+            let line = source.line(line_num).unwrap_or("");
+            let mut run = String::new();
+            let mut run_tone: Option<Tone> = None;
+            for (col, chr) in line.chars().enumerate() {
+                let tone = annotations
+                    .iter()
+                    .find(|annotation| annotation.covers(line_num, col))
+                    .map(|annotation| annotation.tone);
+                if tone != run_tone && !run.is_empty() {
+                    output += &paint_run(renderer, run_tone, &std::mem::take(&mut run));
+                }
+                run_tone = tone;
+                run.push(chr);
+            }
+            output += &paint_run(renderer, run_tone, &run);
+            output += "\n";
+
+            let prefix = |open: &dyn Fn(&Annotation) -> char| {
+                renderer.paint(
+                    &format!(
+                        "{}{} ",
+                        padding, chars.dotted
+                    ),
+                    Style::Frame,
+                ) + &margin(open)
+            };
+            let open_below = |annotation: &Annotation| match annotation.start.line_num <= line_num
+                && line_num <= annotation.end.line_num
+            {
+                | true => chars.vertical,
+                | false => ' ',
+            };
+
+            let mut single: Vec<&Annotation> = annotations
+                .iter()
+                .filter(|annotation| !annotation.is_multiline() && annotation.start.line_num == line_num)
+                .collect();
+            single.sort_by_key(|annotation| annotation.start.col);
+            for annotation in single {
+                let start = annotation.start.col;
+                let len = annotation.end.col.saturating_sub(start);
+                for row in underline(renderer, start, len, annotation.text, annotation.tone) {
+                    output += &(prefix(&open_below) + &row + "\n");
+                }
+            }
+
+            for (closing, annotation) in multiline.iter().enumerate() {
+                if annotation.end.line_num != line_num {
+                    continue;
+                }
+                let mut row = renderer.paint(
+                    &format!(
+                        "{}{} ",
+                        padding, chars.dotted
+                    ),
+                    Style::Frame,
+                );
+                // spans closed by earlier rows are no longer drawn
+                for open in multiline.iter().take(closing) {
+                    let cell = match open.end.line_num == line_num {
+                        | true => ' ',
+                        | false => open_below(open),
+                    };
+                    row += &renderer.paint(&cell.to_string(), Style::Marker(open.tone));
+                }
+                let width = multiline.len() - closing + annotation.end.col;
+                row += &renderer.paint(
+                    &format!(
+                        "{}{} ",
+                        chars.bottom_left,
+                        chars.horizontal.to_string().repeat(width)
+                    ),
+                    Style::Marker(annotation.tone),
+                );
+                output += &(row + &renderer.paint(annotation.text, Style::Text) + "\n");
+            }
         }
-        error_line += " ";
-        result.push(self.light_color().paint(error_line).to_string() + &self.details);
-        result
+        output
     }
 
-    fn body(&self) -> String {
-        let line_num = self.cursor.start.line_num.to_string();
-        let padding_width = line_num.len() + 2;
+    fn body(&self, renderer: &dyn Renderer, sources: &SourceMap) -> String {
+        let chars = renderer.charset();
+        let groups = self.annotations(sources);
+        let last_line = groups
+            .iter()
+            .flatten()
+            .map(|annotation| annotation.end.line_num + 1)
+            .max()
+            .unwrap_or(1);
+        let padding = " ".repeat(last_line.to_string().len() + 2);
         let mut output = String::new();
 
-        let mut padding = String::new();
-        for _ in 0..padding_width {
-            padding += " ";
-        }
-        output += &Grey!().paint(padding.clone() + "╭─").to_string();
-        output += &(self.file_name() + "\n");
-        output += &Grey!().paint(padding.clone() + "│\n").to_string();
-        output += &Grey!()
-            .paint(format!(
-                " {} │ ",
-                line_num
-            ))
-            .to_string();
-        output += &(self.line() + "\n");
-
-        let content = self.content();
-        for i in 0..content.len() {
-            output += &Grey!().paint(padding.clone() + "· ").to_string();
-            output += &content[i];
+        for (index, group) in groups.iter().enumerate() {
+            let corner = match index {
+                | 0 => chars.top_left,
+                | _ => chars.branch,
+            };
+            output += &renderer.paint(
+                &format!(
+                    "{}{}{}",
+                    padding, corner, chars.horizontal
+                ),
+                Style::Frame,
+            );
+            output += &(Self::file_name(renderer, sources, &group[0]) + "\n");
+            output += &renderer.paint(
+                &format!(
+                    "{}{}",
+                    padding, chars.vertical
+                ),
+                Style::Frame,
+            );
             output += "\n";
+            output += &Self::snippet(renderer, sources, group, &padding);
         }
-        let mut footer = String::new();
-        for _ in 0..padding_width {
-            footer += "─";
+
+        for (kind, texts) in [("note", &self.notes), ("help", &self.help)] {
+            for text in texts {
+                output += &renderer.paint(&(padding.clone() + "= "), Style::Frame);
+                output += &renderer.paint(&format!("{}: ", kind), Style::Kind);
+                output += &renderer.paint(text, Style::Text);
+                output += "\n";
+            }
+        }
+
+        output += &renderer.paint(
+            &format!(
+                "{}{}",
+                chars.horizontal.to_string().repeat(padding.len()),
+                chars.bottom_right
+            ),
+            Style::Frame,
+        );
+        output
+    }
+
+    /// The message drawn with a renderer, showing the lines of code it is
+    /// about.
+    pub fn render(&self, renderer: &dyn Renderer, sources: &SourceMap) -> String {
+        renderer.finish(
+            self.header(renderer)
+                + &renderer.paint(self.message_type.name(), Style::Text)
+                + "\n"
+                + &self.body(renderer, sources),
+        )
+    }
+
+    /// The message in the JSON format described in the README. Lines and
+    /// columns start at 1, and the end is exclusive.
+    pub fn to_json(&self, sources: &SourceMap, columns: Columns) -> JsonValue {
+        let span = |cursor: &Cursor| {
+            let (start, end) = cursor.resolve(sources, columns);
+            let position = |position: Position| {
+                object! {
+                    line: position.line_num + 1,
+                    column: position.col + 1
+                }
+            };
+            (position(start), position(end))
+        };
+        let (start, end) = span(&self.cursor);
+        object! {
+            code: self.message_type.code(),
+            severity: match self.is_error() {
+                | true => "error",
+                | false => "warning",
+            },
+            title: self.message_type.name(),
+            message: self.details.as_str(),
+            file: sources.name(self.cursor.file),
+            start: start,
+            end: end,
+            notes: self.notes.clone(),
+            help: self.help.clone(),
+            labels: self
+                .labels
+                .iter()
+                .map(|label| {
+                    let (start, end) = span(&label.cursor);
+                    object! {
+                        message: label.text.as_str(),
+                        file: sources.name(label.cursor.file),
+                        start: start,
+                        end: end
+                    }
+                })
+                .collect::<Vec<JsonValue>>()
         }
-        footer += "╯";
-        output += &Grey!().paint(footer).to_string();
+    }
+
+    /// Points at another span related to the message.
+    pub fn with_label(mut self, cursor: Cursor, text: &str) -> Self {
+        self.labels.push(Label {
+            cursor,
+            text: text.to_string(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
 
-        return output;
+    /// Suggests how to fix the problem.
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help.push(help.to_string());
+        self
     }
 
     pub fn error(message_type: MessageType, details: &str, cursor: Cursor) -> Self {
         return Self {
             details: details.to_string(),
             message_type,
+            severity: Severity::Error,
             cursor,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
         };
     }
-}
-impl ToString for Message {
-    fn to_string(&self) -> String {
-        self.header() + self.message_type.name() + "\n" + &self.body()
+
+    pub fn warning(message_type: MessageType, details: &str, cursor: Cursor) -> Self {
+        Self {
+            details: details.to_string(),
+            message_type,
+            severity: Severity::Warning,
+            cursor,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
     }
 }
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use crate::cursor::Position;
+
+/// A file added to a `SourceMap`, which cursors refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct FileId(usize);
+
+/// What a column counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Columns {
+    /// Unicode scalar values, as diagnostics are drawn with
+    #[default]
+    Scalars,
+    /// UTF-16 code units, as the language server protocol counts by default
+    Utf16,
+}
+
+pub struct SourceFile {
+    pub name: Arc<str>,
+    pub text: Arc<str>,
+    /// Byte index of the start of every line
+    line_starts: Vec<usize>,
+}
+impl SourceFile {
+    fn new(name: Arc<str>, text: Arc<str>) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self {
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    /// The line a byte index is on, starting at 0.
+    pub fn line_num(&self, index: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= index) - 1
+    }
+
+    /// A line without its line break, `None` past the last one.
+    pub fn line(&self, line_num: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line_num)?;
+        let end = self
+            .line_starts
+            .get(line_num + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        Some(&self.text[start..end])
+    }
+
+    /// The line and column of a byte index, both starting at 0. Indices past
+    /// the end are taken as the end.
+    pub fn position(&self, index: usize, columns: Columns) -> Position {
+        let index = index.min(self.text.len());
+        let line_num = self.line_num(index);
+        let before = &self.text[self.line_starts[line_num]..index];
+        let col = match columns {
+            | Columns::Scalars => before.chars().count(),
+            | Columns::Utf16 => before.encode_utf16().count(),
+        };
+        Position {
+            index,
+            line_num,
+            col,
+        }
+    }
+}
+
+/// Every file compiled together, so that cursors only need to hold a `FileId`
+/// and lines and columns are only worked out when a diagnostic is shown.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<Arc<str>>, text: impl Into<Arc<str>>) -> FileId {
+        self.files.push(SourceFile::new(name.into(), text.into()));
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn get(&self, file: FileId) -> &SourceFile {
+        &self.files[file.0]
+    }
+
+    pub fn name(&self, file: FileId) -> &str {
+        &self.get(file).name
+    }
+
+    pub fn text(&self, file: FileId) -> &str {
+        &self.get(file).text
+    }
+
+    /// The line and column of a byte index of a file, both starting at 0.
+    pub fn position(&self, file: FileId, index: usize, columns: Columns) -> Position {
+        self.get(file).position(index, columns)
+    }
+}
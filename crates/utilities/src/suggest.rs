@@ -0,0 +1,72 @@
+use std::cmp::min;
+
+/// Number of insertions, deletions, substitutions and swaps of adjacent
+/// characters turning `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // distances[i][j] is the distance between the first i chars of a and the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = match a[i - 1] == b[j - 1] {
+                | true => 0,
+                | false => 1,
+            };
+            let mut distance = min(
+                min(distances[i - 1][j] + 1, distances[i][j - 1] + 1),
+                distances[i - 1][j - 1] + substitution,
+            );
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = min(distance, distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// Candidates close enough to `name` to be what was meant, closest first, at
+/// most three of them.
+pub fn similar<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    let mut found: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    found.sort();
+    found.dedup();
+    found
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Help text offering names instead of a wrong one, like "did you mean 'a' or
+/// 'b'?", `None` if there are none.
+pub fn did_you_mean(names: &[&str]) -> Option<String> {
+    let quoted: Vec<String> = names
+        .iter()
+        .map(|name| format!("'{}'", name))
+        .collect();
+    match quoted.split_last() {
+        | None => None,
+        | Some((last, [])) => Some(format!("did you mean {}?", last)),
+        | Some((last, rest)) => Some(format!(
+            "did you mean {} or {}?",
+            rest.join(", "),
+            last
+        )),
+    }
+}